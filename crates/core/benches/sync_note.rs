@@ -0,0 +1,111 @@
+//! Measures how the cost of `save_note` and `sync_note` scales with vault size.
+//!
+//! Run with `cargo bench -p zinnia_core --bench sync_note`. The large vault has
+//! 50,000 notes unless `ZINNIA_BENCH_NOTES` says otherwise. The run fails if either
+//! operation is much slower in the large vault than in a small one, which would
+//! mean its cost depends on the size of the vault again.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+use zinnia_core::NotesApi;
+
+const SMALL_VAULT: usize = 100;
+const DEFAULT_LARGE_VAULT: usize = 50_000;
+/// Notes per top-level folder
+const FOLDER_SIZE: usize = 500;
+const SAMPLES: usize = 200;
+/// How many times slower an operation may be in the large vault
+const MAX_SLOWDOWN: f64 = 5.0;
+
+/// Writes a vault of `notes` notes, spread over top-level folders, and indexes it.
+/// Returns the API and the paths of the notes inside the folders.
+fn generate_vault(root: &Path, notes: usize) -> (NotesApi, Vec<String>) {
+    let mut paths = Vec::with_capacity(notes);
+    for i in 0..notes {
+        let folder = format!("folder-{}", i / FOLDER_SIZE);
+        if i % FOLDER_SIZE == 0 {
+            std::fs::create_dir_all(root.join(&folder)).unwrap();
+            std::fs::write(root.join(&folder).join("_index.md"), "# Folder\n").unwrap();
+            paths.push(folder.clone());
+        }
+
+        let path = format!("{}/note-{}", folder, i);
+        std::fs::create_dir_all(root.join(&path)).unwrap();
+        std::fs::write(
+            root.join(&path).join("_index.md"),
+            format!("# Note {}\n\nSee [[{}]]. #bench\n", i, folder),
+        )
+        .unwrap();
+        paths.push(path);
+    }
+
+    let mut api = NotesApi::new(root).unwrap();
+    api.sync_notes(&paths).unwrap();
+    paths.retain(|p| p.contains('/'));
+    (api, paths)
+}
+
+fn median(mut samples: Vec<Duration>) -> Duration {
+    samples.sort();
+    samples[samples.len() / 2]
+}
+
+/// Returns the median time of `save_note` and of `sync_note` after an external edit.
+fn measure(root: &Path, api: &mut NotesApi, paths: &[String]) -> (Duration, Duration) {
+    let step = (paths.len() / SAMPLES).max(1);
+    let mut saves = Vec::with_capacity(SAMPLES);
+    let mut syncs = Vec::with_capacity(SAMPLES);
+
+    for (i, path) in paths.iter().step_by(step).take(SAMPLES).enumerate() {
+        let started = Instant::now();
+        api.save_note(path, &format!("# Saved {}\n", i)).unwrap();
+        saves.push(started.elapsed());
+
+        std::fs::write(
+            root.join(path).join("_index.md"),
+            format!("# Edited {}\n", i),
+        )
+        .unwrap();
+        let started = Instant::now();
+        assert!(api.sync_note(path).unwrap());
+        syncs.push(started.elapsed());
+    }
+
+    (median(saves), median(syncs))
+}
+
+fn run(notes: usize) -> (Duration, Duration) {
+    let temp_dir = TempDir::new().unwrap();
+
+    let started = Instant::now();
+    let (mut api, paths) = generate_vault(temp_dir.path(), notes);
+    println!("{:>6} notes: indexed in {:?}", notes, started.elapsed());
+
+    let (save, sync) = measure(temp_dir.path(), &mut api, &paths);
+    println!(
+        "{:>6} notes: save_note {:?}, sync_note {:?}",
+        notes, save, sync
+    );
+    (save, sync)
+}
+
+fn main() {
+    let large_vault = std::env::var("ZINNIA_BENCH_NOTES")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_LARGE_VAULT);
+
+    let (small_save, small_sync) = run(SMALL_VAULT);
+    let (large_save, large_sync) = run(large_vault);
+
+    let save_slowdown = large_save.as_secs_f64() / small_save.as_secs_f64();
+    let sync_slowdown = large_sync.as_secs_f64() / small_sync.as_secs_f64();
+    println!("slowdown: save_note {save_slowdown:.2}x, sync_note {sync_slowdown:.2}x");
+
+    assert!(
+        save_slowdown < MAX_SLOWDOWN && sync_slowdown < MAX_SLOWDOWN,
+        "saving or syncing a note got more than {MAX_SLOWDOWN}x slower with {large_vault} notes"
+    );
+}
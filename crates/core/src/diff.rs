@@ -0,0 +1,366 @@
+/// Whether the lines of a hunk were added, removed or left unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Unchanged,
+}
+
+/// A run of consecutive lines with the same kind of change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    pub kind: DiffKind,
+    /// 1-based line number of the first line in the old text. For added lines,
+    /// the line in the old text they were inserted before.
+    pub old_start: usize,
+    /// 1-based line number of the first line in the new text. For removed lines,
+    /// the line in the new text they were removed before.
+    pub new_start: usize,
+    /// The lines, without line endings
+    pub lines: Vec<String>,
+}
+
+/// A single step of an edit script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    /// Line in both texts, by index in old and new
+    Keep(usize, usize),
+    /// Line only in the old text, by index
+    Remove(usize),
+    /// Line only in the new text, by index
+    Insert(usize),
+}
+
+/// Computes a line-level diff from `old` to `new`.
+///
+/// Uses Myers' algorithm, so the result has the fewest added and removed lines.
+/// Hunks are returned in order and together cover every line of both texts.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffHunk> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    let mut hunks: Vec<DiffHunk> = Vec::new();
+    // 0-based index of the next line in each text
+    let (mut old_next, mut new_next) = (0, 0);
+
+    for edit in shortest_edit(&old_lines, &new_lines) {
+        let (kind, line) = match edit {
+            Edit::Keep(o, _) => (DiffKind::Unchanged, old_lines[o]),
+            Edit::Remove(o) => (DiffKind::Removed, old_lines[o]),
+            Edit::Insert(n) => (DiffKind::Added, new_lines[n]),
+        };
+
+        match hunks.last_mut() {
+            Some(hunk) if hunk.kind == kind => hunk.lines.push(line.to_string()),
+            _ => hunks.push(DiffHunk {
+                kind,
+                old_start: old_next + 1,
+                new_start: new_next + 1,
+                lines: vec![line.to_string()],
+            }),
+        }
+
+        if kind != DiffKind::Added {
+            old_next += 1;
+        }
+        if kind != DiffKind::Removed {
+            new_next += 1;
+        }
+    }
+
+    hunks
+}
+
+/// Returns the line pairs `(old index, new index)` that a minimal diff keeps
+/// unchanged, in increasing order of both indices.
+pub(crate) fn matching_lines(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    shortest_edit(old, new)
+        .into_iter()
+        .filter_map(|edit| match edit {
+            Edit::Keep(o, n) => Some((o, n)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Finds a shortest edit script turning `old` into `new` (Myers, 1986).
+///
+/// Uses the linear-space variant: the middle of an optimal path splits the texts
+/// in two, and each half is diffed recursively, so memory stays proportional to
+/// the length of the texts however different they are.
+fn shortest_edit(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    push_edits(old, new, 0, 0, &mut edits);
+    edits
+}
+
+/// Appends the edits turning `old` into `new` to `edits`. `old_start` and `new_start`
+/// are the indices of the first lines of `old` and `new` in the full texts.
+fn push_edits(
+    old: &[&str],
+    new: &[&str],
+    old_start: usize,
+    new_start: usize,
+    edits: &mut Vec<Edit>,
+) {
+    // Lines both texts start or end with are kept
+    let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    edits.extend((0..prefix).map(|i| Edit::Keep(old_start + i, new_start + i)));
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    let (o, n) = (old_start + prefix, new_start + prefix);
+    if old_mid.is_empty() {
+        edits.extend((0..new_mid.len()).map(|i| Edit::Insert(n + i)));
+    } else if new_mid.is_empty() {
+        edits.extend((0..old_mid.len()).map(|i| Edit::Remove(o + i)));
+    } else {
+        let (x, y) = middle_snake(old_mid, new_mid);
+        push_edits(&old_mid[..x], &new_mid[..y], o, n, edits);
+        push_edits(&old_mid[x..], &new_mid[y..], o + x, n + y, edits);
+    }
+
+    let (old_end, new_end) = (o + old_mid.len(), n + new_mid.len());
+    edits.extend((0..suffix).map(|i| Edit::Keep(old_end + i, new_end + i)));
+}
+
+/// Finds a point `(x, y)` that an optimal path from the start to the end of the edit
+/// graph passes through, roughly halfway, by searching forward from the start and
+/// backward from the end until the searches meet. `old` and `new` must be non-empty
+/// and differ in their first and last lines.
+fn middle_snake(old: &[&str], new: &[&str]) -> (usize, usize) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m + 1) / 2;
+    let offset = max;
+    let size = 2 * max + 2;
+    let delta = n - m;
+    // With an odd delta the forward search reaches the meeting point first
+    let odd = delta % 2 != 0;
+
+    // forward[k + offset]: furthest x reached on diagonal k from the start;
+    // backward[k + offset]: the same from the end, with x counted from the end
+    let mut forward = vec![-1isize; size as usize];
+    let mut backward = vec![-1isize; size as usize];
+    forward[offset as usize + 1] = 0;
+    backward[offset as usize + 1] = 0;
+
+    // Diagonals that ran off the edit graph, trimmed from later steps
+    let (mut forward_start, mut forward_end) = (0, 0);
+    let (mut backward_start, mut backward_end) = (0, 0);
+
+    for d in 0..max {
+        for k in (-d + forward_start..=d - forward_end).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[i] = x;
+
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else if odd {
+                let j = offset + delta - k;
+                if (0..size).contains(&j)
+                    && backward[j as usize] != -1
+                    && x >= n - backward[j as usize]
+                {
+                    return (x as usize, y as usize);
+                }
+            }
+        }
+
+        for k in (-d + backward_start..=d - backward_end).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && backward[i - 1] < backward[i + 1]) {
+                backward[i + 1]
+            } else {
+                backward[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[i] = x;
+
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else if !odd {
+                let j = offset + delta - k;
+                if (0..size).contains(&j) && forward[j as usize] != -1 {
+                    let forward_x = forward[j as usize];
+                    let forward_y = forward_x - (delta - k);
+                    if forward_x >= n - x {
+                        return (forward_x as usize, forward_y as usize);
+                    }
+                }
+            }
+        }
+    }
+
+    // The searches always meet; if they somehow didn't, replace everything
+    (n as usize, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(kind: DiffKind, old_start: usize, new_start: usize, lines: &[&str]) -> DiffHunk {
+        DiffHunk {
+            kind,
+            old_start,
+            new_start,
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_replaced_line() {
+        let hunks = diff_lines("a\nb\nc\n", "a\nB\nc\n");
+        assert_eq!(
+            hunks,
+            vec![
+                hunk(DiffKind::Unchanged, 1, 1, &["a"]),
+                hunk(DiffKind::Removed, 2, 2, &["b"]),
+                hunk(DiffKind::Added, 3, 2, &["B"]),
+                hunk(DiffKind::Unchanged, 3, 3, &["c"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_insert_and_delete() {
+        assert_eq!(
+            diff_lines("a\nc", "a\nb\nc"),
+            vec![
+                hunk(DiffKind::Unchanged, 1, 1, &["a"]),
+                hunk(DiffKind::Added, 2, 2, &["b"]),
+                hunk(DiffKind::Unchanged, 2, 3, &["c"]),
+            ]
+        );
+        assert_eq!(
+            diff_lines("a\nb\nc\nd", "a\nd"),
+            vec![
+                hunk(DiffKind::Unchanged, 1, 1, &["a"]),
+                hunk(DiffKind::Removed, 2, 2, &["b", "c"]),
+                hunk(DiffKind::Unchanged, 4, 2, &["d"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_empty_and_identical() {
+        assert!(diff_lines("", "").is_empty());
+        assert_eq!(
+            diff_lines("", "new\nlines"),
+            vec![hunk(DiffKind::Added, 1, 1, &["new", "lines"])]
+        );
+        assert_eq!(
+            diff_lines("old", ""),
+            vec![hunk(DiffKind::Removed, 1, 1, &["old"])]
+        );
+        assert_eq!(
+            diff_lines("same\ntext", "same\ntext"),
+            vec![hunk(DiffKind::Unchanged, 1, 1, &["same", "text"])]
+        );
+    }
+
+    #[test]
+    fn test_diff_is_minimal() {
+        let old = "x\na\nb\nc\ny\nz";
+        let new = "a\nb\nq\nc\nz\nw";
+        let changed: usize = diff_lines(old, new)
+            .iter()
+            .filter(|h| h.kind != DiffKind::Unchanged)
+            .map(|h| h.lines.len())
+            .sum();
+        // Remove x and y, add q and w
+        assert_eq!(changed, 4);
+    }
+
+    /// Length of the longest common subsequence, by dynamic programming.
+    fn lcs_len(old: &[&str], new: &[&str]) -> usize {
+        let mut table = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                table[i][j] = if old[i] == new[j] {
+                    table[i + 1][j + 1] + 1
+                } else {
+                    table[i + 1][j].max(table[i][j + 1])
+                };
+            }
+        }
+        table[0][0]
+    }
+
+    #[test]
+    fn test_shortest_edit_matches_lcs() {
+        // Small alphabets give lots of repeated lines and ties between paths
+        let mut seed: u64 = 42;
+        let mut next = move |bound: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+        let alphabet = ["a", "b", "c"];
+
+        for _ in 0..2000 {
+            let old: Vec<&str> = (0..next(12)).map(|_| alphabet[next(3) as usize]).collect();
+            let new: Vec<&str> = (0..next(12)).map(|_| alphabet[next(3) as usize]).collect();
+
+            let edits = shortest_edit(&old, &new);
+            let (mut o, mut n) = (0, 0);
+            for edit in &edits {
+                match *edit {
+                    Edit::Keep(i, j) => {
+                        assert_eq!((i, j), (o, n));
+                        assert_eq!(old[i], new[j]);
+                        o += 1;
+                        n += 1;
+                    }
+                    Edit::Remove(i) => {
+                        assert_eq!(i, o);
+                        o += 1;
+                    }
+                    Edit::Insert(j) => {
+                        assert_eq!(j, n);
+                        n += 1;
+                    }
+                }
+            }
+            assert_eq!((o, n), (old.len(), new.len()));
+
+            let kept = edits.iter().filter(|e| matches!(e, Edit::Keep(..))).count();
+            assert_eq!(kept, lcs_len(&old, &new), "{:?} -> {:?}", old, new);
+        }
+    }
+
+    #[test]
+    fn test_diff_unrelated_texts() {
+        let old: String = (0..3000).map(|i| format!("old {}\n", i)).collect();
+        let new: String = (0..3000).map(|i| format!("new {}\n", i)).collect();
+        let hunks = diff_lines(&old, &new);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].lines.len(), 3000);
+        assert_eq!(hunks[1].lines.len(), 3000);
+    }
+}
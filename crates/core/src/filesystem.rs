@@ -1,12 +1,37 @@
-use std::fs;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::thread;
 use std::time::SystemTime;
 
+/// Directory under the notes root holding removed notes that can still be restored
+pub(crate) const STASH_DIR: &str = ".notes-undo";
+
+/// File in a stash session's directory, locked while the session's owner is running
+const STASH_LOCK: &str = ".lock";
+
+/// Most threads used to walk the notes tree
+const MAX_SCAN_THREADS: usize = 8;
+
 #[derive(Debug, Clone)]
 pub struct FSNoteMetadata {
     pub path: String,
     pub mtime: SystemTime,
+    /// Size of the note's `_index.md` in bytes
+    pub size: u64,
+    /// Inode of the note's `_index.md`, which stays the same when the note is moved.
+    /// None on platforms without inodes.
+    pub inode: Option<u64>,
+}
+
+/// Directories waiting to be scanned by `scan_all`'s worker threads.
+struct ScanQueue {
+    /// Directories to scan, with their note paths
+    dirs: Vec<(PathBuf, String)>,
+    /// Directories queued or being scanned; the walk is done when this reaches zero
+    pending: usize,
+    error: Option<io::Error>,
 }
 
 // Helper function to get parent path from a path string
@@ -21,16 +46,126 @@ fn get_parent_path(path: &str) -> Option<String> {
         .map(|p| p.to_string_lossy().to_string())
 }
 
+/// Replaces the file at `path` with `data` so that a crash leaves either the old or
+/// the new content, never a partial write.
+///
+/// The data goes to a temp file next to `path`, which is fsync'd and renamed over
+/// `path`. On Linux the directory is fsync'd too, so the rename itself is durable.
+/// `write` does the actual writing, which lets tests inject failures.
+fn write_atomic<W>(path: &Path, data: &[u8], write: W) -> io::Result<()>
+where
+    W: FnOnce(&mut File, &[u8]) -> io::Result<()>,
+{
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    // A temp file left behind by a crash is truncated by the next write
+    let temp_path = dir.join(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        write(&mut file, data)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        fs::remove_file(&temp_path).ok(); // Ignore errors
+        return result;
+    }
+
+    #[cfg(target_os = "linux")]
+    File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
+/// The file's inode number, if the platform has them.
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Recursively copies the directory `from` to `to`.
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// This instance's directory in the stash. It's created, and locked so other
+/// instances can tell it's in use, when the first note is stashed, and deleted
+/// when the session is dropped.
+#[derive(Debug)]
+struct StashSession {
+    dir: PathBuf,
+    lock: Mutex<Option<File>>,
+}
+
+impl StashSession {
+    fn new(root_path: &Path) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let name = format!("{}-{}", std::process::id(), nanos);
+        Self {
+            dir: root_path.join(STASH_DIR).join(name),
+            lock: Mutex::new(None),
+        }
+    }
+
+    /// Creates and locks the session directory, if that hasn't happened yet.
+    fn open(&self) -> io::Result<&Path> {
+        let mut lock = self.lock.lock().unwrap();
+        if lock.is_none() {
+            fs::create_dir_all(&self.dir)?;
+            let file = File::create(self.dir.join(STASH_LOCK))?;
+            // Without file locking, other instances treat the session as in use
+            file.try_lock().ok();
+            *lock = Some(file);
+        }
+        Ok(&self.dir)
+    }
+}
+
+impl Drop for StashSession {
+    fn drop(&mut self) {
+        if self.lock.get_mut().is_ok_and(|lock| lock.is_some()) {
+            fs::remove_dir_all(&self.dir).ok(); // Ignore errors
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NoteFilesystem {
     root_path: PathBuf,
+    stash: StashSession,
 }
 
 impl NoteFilesystem {
     pub fn new<P: AsRef<Path>>(root_path: P) -> io::Result<Self> {
         let root_path = root_path.as_ref().to_path_buf();
         fs::create_dir_all(&root_path)?;
-        Ok(Self { root_path })
+        let stash = StashSession::new(&root_path);
+        Ok(Self { root_path, stash })
     }
 
     pub fn root_path(&self) -> &Path {
@@ -42,12 +177,38 @@ impl NoteFilesystem {
         fs::read_to_string(fs_path)
     }
 
+    pub fn note_exists(&self, path: &str) -> bool {
+        self.note_to_fs_path(path).is_file()
+    }
+
+    /// Whether anything (a note directory, attachment or other file) exists at `path`.
+    pub fn path_exists(&self, path: &str) -> bool {
+        self.root_path.join(path).exists()
+    }
+
+    /// Lists the names of the files and directories in a note's directory.
+    pub fn list_entries(&self, path: &str) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(self.root_path.join(path))? {
+            names.push(entry?.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Whether `path` is a directory, as opposed to a file.
+    pub fn is_dir(&self, path: &str) -> bool {
+        self.root_path.join(path).is_dir()
+    }
+
     pub fn write_note(&self, path: &str, content: &str) -> io::Result<()> {
         let fs_path = self.note_to_fs_path(path);
         if let Some(parent) = fs_path.parent() {
             fs::create_dir_all(parent)?;
         }
-        fs::write(fs_path, content)
+        write_atomic(&fs_path, content.as_bytes(), |file, data| {
+            file.write_all(data)
+        })
     }
 
     pub fn create_note(&self, path: &str) -> io::Result<()> {
@@ -69,6 +230,7 @@ impl NoteFilesystem {
         fs::remove_dir_all(dir_path)
     }
 
+    #[allow(clippy::manual_flatten, clippy::collapsible_if)]
     pub fn cleanup_unused_attachments(&self, path: &str, content: &str) -> io::Result<()> {
         // Get the note's directory
         let note_dir = if path.is_empty() {
@@ -169,9 +331,115 @@ impl NoteFilesystem {
             .map_err(|e| io::Error::other(format!("Failed to move note to trash: {}", e)))
     }
 
+    /// Moves a note directory into the stash, under `stash_id`.
+    pub fn stash_note(&self, path: &str, stash_id: u64) -> io::Result<()> {
+        let stash_path = self.stash.open()?.join(stash_id.to_string());
+        fs::rename(self.root_path.join(path), stash_path)
+    }
+
+    /// Copies a note directory into the stash, under `stash_id`, leaving the original.
+    pub fn copy_to_stash(&self, path: &str, stash_id: u64) -> io::Result<()> {
+        let stash_path = self.stash.open()?.join(stash_id.to_string());
+        let result = copy_dir(&self.root_path.join(path), &stash_path);
+        if result.is_err() {
+            self.drop_stash(stash_id).ok();
+        }
+        result
+    }
+
+    /// Moves a stashed note directory back to `path`.
+    pub fn restore_stash(&self, stash_id: u64, path: &str) -> io::Result<()> {
+        let dir_path = self.root_path.join(path);
+        if dir_path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "Note already exists",
+            ));
+        }
+        if let Some(parent) = dir_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(self.stash_path(stash_id), dir_path)
+    }
+
+    pub fn drop_stash(&self, stash_id: u64) -> io::Result<()> {
+        fs::remove_dir_all(self.stash_path(stash_id))
+    }
+
+    /// Deletes notes stashed by instances that are no longer running, leaving the
+    /// stashes of other open instances alone.
+    pub fn clear_stale_stashes(&self) -> io::Result<()> {
+        let entries = match fs::read_dir(self.root_path.join(STASH_DIR)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            entries => entries?,
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if path == self.stash.dir {
+                continue;
+            }
+            // A session is in use while its owner holds the lock
+            let in_use =
+                File::open(path.join(STASH_LOCK)).is_ok_and(|lock| lock.try_lock().is_err());
+            if in_use {
+                continue;
+            }
+            if path.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a single note's metadata, without scanning the rest of the tree.
+    pub fn stat_note(&self, path: &str) -> io::Result<FSNoteMetadata> {
+        let metadata = fs::metadata(self.note_to_fs_path(path))?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "Note does not exist",
+            ));
+        }
+        Ok(FSNoteMetadata {
+            path: path.to_string(),
+            mtime: metadata.modified()?,
+            size: metadata.len(),
+            inode: inode(&metadata),
+        })
+    }
+
+    /// Returns every note in the tree, sorted by path.
+    ///
+    /// Directories are walked by a pool of threads, since scanning a large vault
+    /// mostly waits on the filesystem.
     pub fn scan_all(&self) -> io::Result<Vec<FSNoteMetadata>> {
-        let mut notes = Vec::new();
-        Self::scan_dir(&self.root_path, "", &mut notes)?;
+        let threads = thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(MAX_SCAN_THREADS);
+        let queue = Mutex::new(ScanQueue {
+            dirs: vec![(self.root_path.clone(), String::new())],
+            pending: 1,
+            error: None,
+        });
+        let ready = Condvar::new();
+
+        let mut notes: Vec<FSNoteMetadata> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| scope.spawn(|| Self::scan_worker(&queue, &ready)))
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        if let Some(e) = queue.into_inner().unwrap().error {
+            return Err(e);
+        }
+        notes.sort_by(|a, b| a.path.cmp(&b.path));
         Ok(notes)
     }
 
@@ -188,6 +456,10 @@ impl NoteFilesystem {
         ancestors
     }
 
+    fn stash_path(&self, stash_id: u64) -> PathBuf {
+        self.stash.dir.join(stash_id.to_string())
+    }
+
     fn note_to_fs_path(&self, path: &str) -> PathBuf {
         if path.is_empty() {
             self.root_path.join("_index.md")
@@ -196,17 +468,62 @@ impl NoteFilesystem {
         }
     }
 
-    fn scan_dir(dir: &Path, prefix: &str, notes: &mut Vec<FSNoteMetadata>) -> io::Result<()> {
+    /// Scans directories from the queue until the whole tree has been scanned.
+    fn scan_worker(queue: &Mutex<ScanQueue>, ready: &Condvar) -> Vec<FSNoteMetadata> {
+        let mut notes = Vec::new();
+        loop {
+            let (dir, prefix) = {
+                let mut queue = queue.lock().unwrap();
+                loop {
+                    if let Some(next) = queue.dirs.pop() {
+                        break next;
+                    }
+                    if queue.pending == 0 {
+                        return notes;
+                    }
+                    queue = ready.wait(queue).unwrap();
+                }
+            };
+
+            let result = Self::scan_dir(&dir, &prefix, &mut notes);
+
+            let mut queue = queue.lock().unwrap();
+            match result {
+                Ok(subdirs) => {
+                    queue.pending += subdirs.len();
+                    queue.dirs.extend(subdirs);
+                }
+                Err(e) => {
+                    // Give up on the rest of the walk
+                    queue.error.get_or_insert(e);
+                    queue.pending -= queue.dirs.len();
+                    queue.dirs.clear();
+                }
+            }
+            queue.pending -= 1;
+            ready.notify_all();
+        }
+    }
+
+    /// Records the note in `dir`, if there is one, and returns the subdirectories
+    /// to scan next.
+    fn scan_dir(
+        dir: &Path,
+        prefix: &str,
+        notes: &mut Vec<FSNoteMetadata>,
+    ) -> io::Result<Vec<(PathBuf, String)>> {
         let index_path = dir.join("_index.md");
         if index_path.exists() {
             let metadata = fs::metadata(&index_path)?;
-            let mtime = metadata.modified()?;
             notes.push(FSNoteMetadata {
                 path: prefix.to_string(),
-                mtime,
+                mtime: metadata.modified()?,
+                size: metadata.len(),
+                inode: inode(&metadata),
             });
         }
 
+        let mut subdirs = Vec::new();
         for entry in fs::read_dir(dir)? {
             let entry = entry?;
             let metadata = entry.metadata()?;
@@ -215,7 +532,7 @@ impl NoteFilesystem {
                 let name = entry.file_name().to_string_lossy().to_string();
 
                 // Skip special directories
-                if name == "_backups" {
+                if name == "_backups" || name == STASH_DIR {
                     continue;
                 }
 
@@ -224,11 +541,11 @@ impl NoteFilesystem {
                 } else {
                     format!("{}/{}", prefix, name)
                 };
-                Self::scan_dir(&entry.path(), &new_prefix, notes)?;
+                subdirs.push((entry.path(), new_prefix));
             }
         }
 
-        Ok(())
+        Ok(subdirs)
     }
 }
 
@@ -290,6 +607,40 @@ mod tests {
         assert!(fs.read_note("parent/child").is_err());
     }
 
+    #[test]
+    fn test_stat_note() {
+        let temp_dir = TempDir::new().unwrap();
+        let fs = NoteFilesystem::new(temp_dir.path()).unwrap();
+
+        fs.create_note("parent").unwrap();
+        fs.create_note("parent/child").unwrap();
+        fs.write_note("parent/child", "content").unwrap();
+
+        let metadata = fs.stat_note("parent/child").unwrap();
+        assert_eq!(metadata.path, "parent/child");
+        let scanned = fs
+            .scan_all()
+            .unwrap()
+            .into_iter()
+            .find(|m| m.path == "parent/child")
+            .unwrap();
+        assert_eq!(metadata.mtime, scanned.mtime);
+        assert_eq!(metadata.inode, scanned.inode);
+
+        // A directory without _index.md isn't a note
+        std::fs::create_dir_all(temp_dir.path().join("parent/folder")).unwrap();
+        for missing in ["parent/folder", "parent/missing"] {
+            assert_eq!(
+                fs.stat_note(missing).unwrap_err().kind(),
+                io::ErrorKind::NotFound
+            );
+        }
+
+        // The inode stays with the note when it's moved
+        fs.rename_note("parent", "moved").unwrap();
+        assert_eq!(fs.stat_note("moved/child").unwrap().inode, metadata.inode);
+    }
+
     #[test]
     fn test_scan_all() {
         let temp_dir = TempDir::new().unwrap();
@@ -309,6 +660,30 @@ mod tests {
         assert!(paths.contains(&"projects/rust-app"));
     }
 
+    #[test]
+    fn test_scan_all_wide_and_deep_tree() {
+        let temp_dir = TempDir::new().unwrap();
+        let fs = NoteFilesystem::new(temp_dir.path()).unwrap();
+
+        let mut expected = Vec::new();
+        for i in 0..20 {
+            let mut path = format!("area-{:02}", i);
+            for depth in 0..5 {
+                fs.create_note(&path).unwrap();
+                expected.push(path.clone());
+                path = format!("{}/level-{}", path, depth);
+            }
+        }
+        // Folders without notes are walked too
+        std::fs::create_dir_all(temp_dir.path().join("plain/folder")).unwrap();
+        fs.create_note("plain/folder/note").unwrap();
+        expected.push("plain/folder/note".to_string());
+        expected.sort();
+
+        let scanned: Vec<String> = fs.scan_all().unwrap().into_iter().map(|m| m.path).collect();
+        assert_eq!(scanned, expected);
+    }
+
     #[test]
     fn test_special_characters_in_path() {
         let temp_dir = TempDir::new().unwrap();
@@ -536,4 +911,108 @@ mod tests {
         // Run cleanup (should not error even with no attachments dir)
         fs.cleanup_unused_attachments("test-note", content).unwrap();
     }
+
+    #[test]
+    fn test_write_note_replaces_atomically() {
+        let temp_dir = TempDir::new().unwrap();
+        let fs = NoteFilesystem::new(temp_dir.path()).unwrap();
+
+        fs.write_note("test", "first").unwrap();
+        fs.write_note("test", "second").unwrap();
+        assert_eq!(fs.read_note("test").unwrap(), "second");
+
+        // No temp file is left next to the note
+        let entries: Vec<_> = fs::read_dir(temp_dir.path().join("test"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec!["_index.md"]);
+    }
+
+    #[test]
+    fn test_interrupted_write_keeps_previous_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let fs = NoteFilesystem::new(temp_dir.path()).unwrap();
+        fs.write_note("test", "previous content").unwrap();
+
+        // Fail halfway through writing, as a crash or full disk would
+        let index_path = temp_dir.path().join("test/_index.md");
+        let result = write_atomic(&index_path, b"new content", |file, data| {
+            file.write_all(&data[..data.len() / 2])?;
+            Err(io::Error::other("interrupted"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs.read_note("test").unwrap(), "previous content");
+        assert!(!temp_dir.path().join("test/._index.md.tmp").exists());
+
+        // A temp file left behind by a crash doesn't affect reads or later writes
+        fs::write(temp_dir.path().join("test/._index.md.tmp"), "partial").unwrap();
+        assert_eq!(fs.read_note("test").unwrap(), "previous content");
+        fs.write_note("test", "new content").unwrap();
+        assert_eq!(fs.read_note("test").unwrap(), "new content");
+    }
+
+    #[test]
+    fn test_stash_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let fs = NoteFilesystem::new(temp_dir.path()).unwrap();
+
+        fs.write_note("note", "Content").unwrap();
+        fs.write_note("note/child", "Child").unwrap();
+        fs::create_dir_all(temp_dir.path().join("note/_attachments")).unwrap();
+        fs::write(temp_dir.path().join("note/_attachments/a.png"), b"png").unwrap();
+
+        fs.stash_note("note", 1).unwrap();
+        assert!(fs.read_note("note").is_err());
+        // Stashed notes aren't picked up by scans
+        assert!(fs.scan_all().unwrap().is_empty());
+
+        fs.restore_stash(1, "note").unwrap();
+        assert_eq!(fs.read_note("note/child").unwrap(), "Child");
+        assert!(temp_dir.path().join("note/_attachments/a.png").exists());
+
+        // Copies leave the original in place
+        fs.copy_to_stash("note", 2).unwrap();
+        assert_eq!(fs.read_note("note").unwrap(), "Content");
+        assert!(fs.restore_stash(2, "note").is_err());
+        fs.delete_note("note").unwrap();
+        fs.restore_stash(2, "note").unwrap();
+        assert_eq!(fs.read_note("note/child").unwrap(), "Child");
+
+        // Dropping the filesystem deletes its stash
+        fs.copy_to_stash("note", 3).unwrap();
+        drop(fs);
+        assert_eq!(
+            fs::read_dir(temp_dir.path().join(STASH_DIR))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_clear_stale_stashes() {
+        let temp_dir = TempDir::new().unwrap();
+        let running = NoteFilesystem::new(temp_dir.path()).unwrap();
+        running.create_note("note").unwrap();
+        running.write_note("note", "Content").unwrap();
+        running.copy_to_stash("note", 1).unwrap();
+
+        // Left behind by an instance that crashed, and by older versions
+        let crashed = temp_dir.path().join(STASH_DIR).join("1-1");
+        fs::create_dir_all(crashed.join("0")).unwrap();
+        fs::write(crashed.join(STASH_LOCK), b"").unwrap();
+        fs::create_dir_all(temp_dir.path().join(STASH_DIR).join("0")).unwrap();
+
+        let fs = NoteFilesystem::new(temp_dir.path()).unwrap();
+        fs.clear_stale_stashes().unwrap();
+        assert!(!crashed.exists());
+        assert!(!temp_dir.path().join(STASH_DIR).join("0").exists());
+
+        // The running instance's stash is kept
+        running.delete_note("note").unwrap();
+        running.restore_stash(1, "note").unwrap();
+        assert_eq!(running.read_note("note").unwrap(), "Content");
+    }
 }
@@ -0,0 +1,418 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// A front matter value. Nested mappings are not supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyValue {
+    Text(String),
+    List(Vec<String>),
+}
+
+impl From<&str> for PropertyValue {
+    fn from(value: &str) -> Self {
+        PropertyValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for PropertyValue {
+    fn from(value: String) -> Self {
+        PropertyValue::Text(value)
+    }
+}
+
+impl From<Vec<String>> for PropertyValue {
+    fn from(values: Vec<String>) -> Self {
+        PropertyValue::List(values)
+    }
+}
+
+/// Front matter properties of a note, keyed by property name.
+pub type Properties = BTreeMap<String, PropertyValue>;
+
+/// Location of the front matter block within a note.
+struct Block {
+    /// Byte range of the lines between the opening and closing `---`
+    inner: Range<usize>,
+    /// Line ending used by the opening delimiter
+    newline: &'static str,
+}
+
+/// A `key: value` entry, spanning its continuation lines.
+struct Entry {
+    key: String,
+    value: PropertyValue,
+    span: Range<usize>,
+}
+
+/// Parses the YAML front matter at the start of a note.
+///
+/// Supports `key: value` scalars (plain or quoted), flow lists (`[a, b]`) and
+/// block lists (`- item` lines). Keys with nested mappings are skipped.
+/// Returns None if the note has no front matter block.
+pub fn parse_front_matter(content: &str) -> Option<Properties> {
+    let block = find_block(content)?;
+    Some(
+        parse_entries(content, &block)
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect(),
+    )
+}
+
+/// Returns the note body without its front matter block.
+pub fn strip_front_matter(content: &str) -> &str {
+    match find_block(content) {
+        Some(block) => {
+            let closing = &content[block.inner.end..];
+            let body_start = closing.find('\n').map(|i| i + 1).unwrap_or(closing.len());
+            &closing[body_start..]
+        }
+        None => content,
+    }
+}
+
+/// Whether `key` can be written as a front matter key and read back unchanged:
+/// non-empty, on one line, without a `:` or surrounding whitespace, and not starting
+/// like a comment, list item or quoted string.
+pub fn is_valid_property_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.trim() == key
+        && !key.contains([':', '\n', '\r'])
+        && !key.starts_with(['#', '-', '"', '\''])
+}
+
+/// Sets a front matter property, leaving everything else in the note byte-for-byte intact.
+///
+/// Replaces the lines of an existing key in place, appends new keys at the end of the
+/// block, and creates a front matter block if the note has none. `key` should pass
+/// [`is_valid_property_key`]; other keys aren't read back.
+pub fn set_front_matter_property(content: &str, key: &str, value: &PropertyValue) -> String {
+    let Some(block) = find_block(content) else {
+        return format!("---\n{}\n---\n{}", format_entry(key, value), content);
+    };
+
+    let line = format!("{}{}", format_entry(key, value), block.newline);
+    let entries = parse_entries(content, &block);
+
+    let span = match entries.iter().find(|entry| entry.key == key) {
+        Some(entry) => entry.span.clone(),
+        None => block.inner.end..block.inner.end,
+    };
+
+    let mut updated = String::with_capacity(content.len() + line.len());
+    updated.push_str(&content[..span.start]);
+    updated.push_str(&line);
+    updated.push_str(&content[span.end..]);
+    updated
+}
+
+fn find_block(content: &str) -> Option<Block> {
+    let first_end = content.find('\n')?;
+    if content[..first_end].trim_end() != "---" {
+        return None;
+    }
+    let newline = if content[..first_end].ends_with('\r') {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    let inner_start = first_end + 1;
+    let mut offset = inner_start;
+    for line in content[inner_start..].split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            return Some(Block {
+                inner: inner_start..offset,
+                newline,
+            });
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+fn parse_entries(content: &str, block: &Block) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    // Key, key line span and inline value of the entry being read
+    let mut current: Option<(String, Range<usize>, String)> = None;
+    let mut items: Vec<String> = Vec::new();
+    let mut nested = false;
+
+    let mut finish =
+        |current: Option<(String, Range<usize>, String)>, items: &mut Vec<String>, nested: bool| {
+            let Some((key, span, inline)) = current else {
+                return;
+            };
+            let value = if !inline.is_empty() {
+                parse_value(&inline)
+            } else if !items.is_empty() {
+                PropertyValue::List(std::mem::take(items))
+            } else if nested {
+                return;
+            } else {
+                PropertyValue::Text(String::new())
+            };
+            items.clear();
+            entries.push(Entry { key, value, span });
+        };
+
+    let mut offset = block.inner.start;
+    for line in content[block.inner.clone()].split_inclusive('\n') {
+        let line_span = offset..offset + line.len();
+        offset += line.len();
+        let text = line.trim_end();
+
+        let is_continuation =
+            text.is_empty() || text.starts_with([' ', '\t']) || text.starts_with('-');
+        if is_continuation {
+            if let Some((_, span, _)) = current.as_mut() {
+                if text.is_empty() {
+                    continue;
+                }
+                span.end = line_span.end;
+                match text.trim_start().strip_prefix('-') {
+                    Some(item) => items.push(parse_scalar(item)),
+                    None => nested = true,
+                }
+            }
+            continue;
+        }
+
+        if text.starts_with('#') {
+            continue;
+        }
+
+        finish(current.take(), &mut items, nested);
+        nested = false;
+
+        if let Some((key, value)) = text.split_once(':') {
+            let key = unquote(key.trim());
+            if !key.is_empty() {
+                current = Some((key, line_span, value.trim().to_string()));
+            }
+        }
+    }
+    finish(current, &mut items, nested);
+
+    entries
+}
+
+fn parse_value(raw: &str) -> PropertyValue {
+    match raw.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        Some(inner) => PropertyValue::List(
+            split_flow_list(inner)
+                .iter()
+                .map(|item| parse_scalar(item))
+                .filter(|item| !item.is_empty())
+                .collect(),
+        ),
+        None => PropertyValue::Text(parse_scalar(raw)),
+    }
+}
+
+/// Splits the inside of a flow list on commas outside of quotes.
+fn split_flow_list(inner: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+
+    for (i, c) in inner.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, ',') => {
+                items.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[start..]);
+
+    items
+}
+
+fn parse_scalar(raw: &str) -> String {
+    let raw = raw.trim();
+    if raw.starts_with(['"', '\'']) {
+        return unquote(raw);
+    }
+
+    // Strip trailing comments from plain scalars
+    let raw = match raw.find(" #") {
+        Some(i) => raw[..i].trim_end(),
+        None => raw,
+    };
+    if raw == "~" || raw == "null" {
+        return String::new();
+    }
+    raw.to_string()
+}
+
+fn unquote(raw: &str) -> String {
+    if let Some(inner) = raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+        return inner.replace("''", "'");
+    }
+
+    if let Some(inner) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+        let mut unescaped = String::with_capacity(inner.len());
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some(other) => unescaped.push(other),
+                None => unescaped.push('\\'),
+            }
+        }
+        return unescaped;
+    }
+
+    raw.to_string()
+}
+
+fn format_entry(key: &str, value: &PropertyValue) -> String {
+    match value {
+        PropertyValue::Text(text) => format!("{}: {}", key, format_scalar(text, false)),
+        PropertyValue::List(items) => {
+            let items: Vec<String> = items.iter().map(|i| format_scalar(i, true)).collect();
+            format!("{}: [{}]", key, items.join(", "))
+        }
+    }
+}
+
+/// Formats a scalar, quoting it only when it would not read back as plain text.
+fn format_scalar(text: &str, in_list: bool) -> String {
+    let needs_quotes = text.is_empty()
+        || text != text.trim()
+        || text.starts_with([
+            '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%', '@', '`', '-', '~',
+        ])
+        || text.contains(": ")
+        || text.contains(" #")
+        || text.contains('\n')
+        || text.ends_with(':')
+        || (in_list && text.contains([',', '[', ']']));
+
+    if needs_quotes {
+        format!(
+            "\"{}\"",
+            text.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        )
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> PropertyValue {
+        PropertyValue::Text(value.to_string())
+    }
+
+    fn list(values: &[&str]) -> PropertyValue {
+        PropertyValue::List(values.iter().map(|v| v.to_string()).collect())
+    }
+
+    #[test]
+    fn test_parse_scalars_and_lists() {
+        let content = "---\n\
+                       status: in progress # comment\n\
+                       due: 2026-01-31\n\
+                       title: \"Quoted: title\"\n\
+                       tags: [work, 'side project']\n\
+                       aliases:\n  - one\n  - two\n\
+                       ---\n# Body\n";
+        let props = parse_front_matter(content).unwrap();
+
+        assert_eq!(props["status"], text("in progress"));
+        assert_eq!(props["due"], text("2026-01-31"));
+        assert_eq!(props["title"], text("Quoted: title"));
+        assert_eq!(props["tags"], list(&["work", "side project"]));
+        assert_eq!(props["aliases"], list(&["one", "two"]));
+    }
+
+    #[test]
+    fn test_no_front_matter() {
+        assert!(parse_front_matter("# Just a note\n").is_none());
+        assert!(parse_front_matter("---\nunclosed: true\n").is_none());
+        assert!(parse_front_matter("text\n---\nkey: value\n---\n").is_none());
+    }
+
+    #[test]
+    fn test_nested_mappings_are_skipped() {
+        let content = "---\nmeta:\n  author: me\nstatus: done\n---\n";
+        let props = parse_front_matter(content).unwrap();
+        assert_eq!(props.len(), 1);
+        assert_eq!(props["status"], text("done"));
+    }
+
+    #[test]
+    fn test_strip_front_matter() {
+        assert_eq!(strip_front_matter("---\na: b\n---\nBody\n"), "Body\n");
+        assert_eq!(strip_front_matter("Body\n"), "Body\n");
+    }
+
+    #[test]
+    fn test_set_existing_property_preserves_rest() {
+        let content = "---\nstatus: todo\ntags:\n  - a\n  - b\ndue: soon\n---\n\n  Body  with *odd*   spacing\n";
+        let updated = set_front_matter_property(content, "tags", &list(&["c"]));
+        assert_eq!(
+            updated,
+            "---\nstatus: todo\ntags: [c]\ndue: soon\n---\n\n  Body  with *odd*   spacing\n"
+        );
+    }
+
+    #[test]
+    fn test_set_new_property_appends_to_block() {
+        let content = "---\r\nstatus: todo\r\n---\r\nBody";
+        let updated = set_front_matter_property(content, "due", &text("2026-02-01"));
+        assert_eq!(
+            updated,
+            "---\r\nstatus: todo\r\ndue: 2026-02-01\r\n---\r\nBody"
+        );
+    }
+
+    #[test]
+    fn test_set_property_creates_block() {
+        let updated = set_front_matter_property("Body\n", "status", &text("done"));
+        assert_eq!(updated, "---\nstatus: done\n---\nBody\n");
+    }
+
+    #[test]
+    fn test_set_property_round_trips_special_values() {
+        for value in [
+            text("needs: quotes"),
+            text("# not a comment"),
+            text(""),
+            text("say \"hi\""),
+            list(&["a, b", "[x]", "plain"]),
+        ] {
+            let updated = set_front_matter_property("", "key", &value);
+            let props = parse_front_matter(&updated).unwrap();
+            assert_eq!(props["key"], value);
+        }
+    }
+
+    #[test]
+    fn test_valid_property_keys() {
+        for key in ["status", "due date", "x-id", "tags#1"] {
+            assert!(is_valid_property_key(key), "{:?}", key);
+            let updated = set_front_matter_property("", key, &text("v"));
+            assert_eq!(parse_front_matter(&updated).unwrap()[key], text("v"));
+        }
+        for key in ["", " status", "a:b", "a\nb", "#tag", "-item", "\"quoted\""] {
+            assert!(!is_valid_property_key(key), "{:?}", key);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime};
+
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+/// A saved version of a note's content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    pub id: i64,
+    /// When this content was saved or synced
+    pub created_at: SystemTime,
+    /// Size of the content in bytes (uncompressed)
+    pub size: usize,
+}
+
+/// Controls how many revisions are kept per note.
+///
+/// A note's latest revision is always kept, so the current content can be restored
+/// after a later bad edit regardless of policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Maximum number of revisions kept per note, or None to keep every revision
+    pub max_revisions: Option<usize>,
+    /// Revisions older than this are pruned, or None to keep revisions forever
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_revisions: Some(100),
+            max_age: None,
+        }
+    }
+}
+
+/// Compresses revision content for storage.
+pub(crate) fn compress(content: &str) -> std::io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(content.as_bytes())?;
+    encoder.finish()
+}
+
+/// Decompresses content produced by [`compress`].
+pub(crate) fn decompress(data: &[u8]) -> std::io::Result<String> {
+    let mut content = String::new();
+    DeflateDecoder::new(data).read_to_string(&mut content)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_round_trip() {
+        let content = "# Note\n\nSome ünïcode 🌼 and repeated text. ".repeat(50);
+        let compressed = compress(&content).unwrap();
+        assert!(compressed.len() < content.len());
+        assert_eq!(decompress(&compressed).unwrap(), content);
+
+        assert_eq!(decompress(&compress("").unwrap()).unwrap(), "");
+    }
+
+    #[test]
+    fn test_decompress_rejects_garbage() {
+        assert!(decompress(b"not deflate data").is_err());
+    }
+}
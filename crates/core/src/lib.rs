@@ -1,12 +1,31 @@
 pub mod default_paths;
+pub mod diff;
 pub mod filesystem;
+pub mod frontmatter;
+pub mod history;
+pub mod markdown;
+pub mod merge;
 pub mod migrations;
 pub mod notes;
+pub mod query;
+pub mod rules;
+mod undo;
 pub mod watcher;
 
 // Re-export main types for convenience
 pub use default_paths::get_default_notes_path;
+pub use diff::{DiffHunk, DiffKind, diff_lines};
 pub use filesystem::{FSNoteMetadata, NoteFilesystem};
+pub use frontmatter::{Properties, PropertyValue};
+pub use history::{RetentionPolicy, Revision};
+pub use markdown::LinkKind;
+pub use merge::{MergeOutcome, MergeRegion, MergeStrategy, merge3};
 pub use migrations::cleanup_br_tags;
-pub use notes::{Error, Note, NoteMetadata, NotesApi, RankingMode, Result};
+pub use notes::{
+    Error, FuzzyMatch, FuzzyMatcher, MatchSource, Note, NoteLink, NoteMetadata, NotesApi,
+    RankingMode, Result, SearchHit, SearchMode, SyncProgress, TagCount, UnarchiveCollision,
+    UnarchiveOptions, Unarchived, UnifiedSearchResult,
+};
+pub use query::{Query, parse_query};
+pub use rules::{ArchiveRule, RuleMatch};
 pub use watcher::{WatcherEvent, setup_watcher};
@@ -0,0 +1,671 @@
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::frontmatter::{
+    PropertyValue, parse_front_matter, set_front_matter_property, strip_front_matter,
+};
+
+/// Matches `[[target]]`, `[[target|alias]]` and `[[target#heading]]`.
+static WIKI_LINK_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[\[([^\[\]\n]+?)\]\]").unwrap());
+
+/// Matches `[text](target)` and `![alt](target)`, with an optional title.
+static MARKDOWN_LINK_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(!?)\[[^\]\n]*\]\(([^()\s]+)(?:\s+"[^"\n]*")?\)"#).unwrap());
+
+/// Matches `#tag` and `#nested/tag` at the start of a line or after whitespace, `[`, `,` or `;`.
+/// `(` is excluded so that `[text](#anchor)` links are not read as tags.
+static TAG_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)(?:^|[\s\[,;])#([\w/-]+)").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// `[[path/to/note]]`, always relative to the notes root
+    Wiki,
+    /// `[text](../other)`, relative to the linking note's directory
+    Markdown,
+}
+
+impl LinkKind {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::Wiki => "wiki",
+            LinkKind::Markdown => "markdown",
+        }
+    }
+
+    pub(crate) fn parse(kind: &str) -> Self {
+        match kind {
+            "markdown" => LinkKind::Markdown,
+            _ => LinkKind::Wiki,
+        }
+    }
+}
+
+/// A link to another note found in a note's content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedLink {
+    /// Note path the link points to, resolved against the notes root
+    pub target: String,
+    pub kind: LinkKind,
+}
+
+/// A link found in content, with the byte range of its raw target text.
+struct LinkMatch {
+    link: ParsedLink,
+    /// Range of the text between `[[` and `]]`, or between `(` and `)`
+    range: Range<usize>,
+}
+
+/// Extracts links to other notes from markdown content.
+///
+/// Recognizes wiki links (`[[path/to/note]]`) and relative markdown links
+/// (`[text](../sibling)`). External URLs, in-page anchors, images and links
+/// into `_attachments` are ignored, as is anything inside code blocks or code spans.
+/// Each target is reported once per kind, in order of first appearance.
+pub fn extract_links(source_path: &str, content: &str) -> Vec<ParsedLink> {
+    let mut links: Vec<ParsedLink> = Vec::new();
+
+    for found in scan_links(source_path, content) {
+        if !links.contains(&found.link) {
+            links.push(found.link);
+        }
+    }
+
+    links
+}
+
+/// Rewrites links in `content` after notes have moved.
+///
+/// `source_path` is where the content lived when its links were written and
+/// `new_source_path` is where it lives after the move. `move_target` maps each
+/// old link target to its new path. Wiki links keep their alias and heading, and
+/// markdown links stay relative (or absolute) as they were written. Links that
+/// already resolve correctly from the new location are left alone.
+///
+/// Returns None if no link needed rewriting.
+pub fn rewrite_links<F>(
+    source_path: &str,
+    new_source_path: &str,
+    content: &str,
+    move_target: F,
+) -> Option<String>
+where
+    F: Fn(&str) -> String,
+{
+    let mut matches = scan_links(source_path, content);
+    matches.sort_by_key(|m| m.range.start);
+
+    let mut rewritten = String::with_capacity(content.len());
+    let mut last = 0;
+    let mut changed = false;
+
+    for found in matches {
+        let raw = &content[found.range.clone()];
+        let new_target = move_target(&found.link.target);
+
+        let replacement = match found.link.kind {
+            LinkKind::Wiki => {
+                if new_target == found.link.target {
+                    continue;
+                }
+                let suffix_start = raw.find(['|', '#']).unwrap_or(raw.len());
+                format!("{}{}", new_target, &raw[suffix_start..])
+            }
+            LinkKind::Markdown => {
+                if resolve_markdown_target(new_source_path, raw).as_deref()
+                    == Some(new_target.as_str())
+                {
+                    continue;
+                }
+                markdown_target_for(new_source_path, &new_target, raw)
+            }
+        };
+
+        rewritten.push_str(&content[last..found.range.start]);
+        rewritten.push_str(&replacement);
+        last = found.range.end;
+        changed = true;
+    }
+
+    if !changed {
+        return None;
+    }
+
+    rewritten.push_str(&content[last..]);
+    Some(rewritten)
+}
+
+fn scan_links(source_path: &str, content: &str) -> Vec<LinkMatch> {
+    let masked = mask_code(content);
+    let mut matches = Vec::new();
+
+    for cap in WIKI_LINK_REGEX.captures_iter(&masked) {
+        let range = cap.get(1).unwrap().range();
+        if let Some(target) = resolve_wiki_target(&content[range.clone()]) {
+            matches.push(LinkMatch {
+                link: ParsedLink {
+                    target,
+                    kind: LinkKind::Wiki,
+                },
+                range,
+            });
+        }
+    }
+
+    for cap in MARKDOWN_LINK_REGEX.captures_iter(&masked) {
+        if !cap[1].is_empty() {
+            continue; // Image
+        }
+        let range = cap.get(2).unwrap().range();
+        if let Some(target) = resolve_markdown_target(source_path, &content[range.clone()]) {
+            matches.push(LinkMatch {
+                link: ParsedLink {
+                    target,
+                    kind: LinkKind::Markdown,
+                },
+                range,
+            });
+        }
+    }
+
+    matches
+}
+
+/// Builds the text of a markdown link target pointing from `source_path` to `target`,
+/// keeping the style (absolute or relative, `.md` suffix, fragment) of `original`.
+fn markdown_target_for(source_path: &str, target: &str, original: &str) -> String {
+    let split = original.find(['#', '?']).unwrap_or(original.len());
+    let (original_path, fragment) = original.split_at(split);
+
+    let mut path = if original_path.starts_with('/') {
+        format!("/{}", target)
+    } else {
+        relative_path(source_path, target)
+    };
+
+    if original_path.ends_with("_index.md") {
+        path = format!("{}/_index.md", path.trim_end_matches('/'));
+    } else if original_path.ends_with(".md") {
+        path.push_str(".md");
+    } else if original_path.ends_with('/') && !path.ends_with('/') {
+        path.push('/');
+    }
+
+    format!("{}{}", path.replace(' ', "%20"), fragment)
+}
+
+/// Returns the relative path from the directory of note `from` to note `to`.
+fn relative_path(from: &str, to: &str) -> String {
+    let from: Vec<&str> = from.split('/').filter(|s| !s.is_empty()).collect();
+    let to: Vec<&str> = to.split('/').filter(|s| !s.is_empty()).collect();
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut segments: Vec<&str> = vec![".."; from.len() - common];
+    segments.extend(&to[common..]);
+
+    if segments.is_empty() {
+        ".".to_string()
+    } else {
+        segments.join("/")
+    }
+}
+
+/// Resolves the inside of `[[...]]` to a note path, dropping any alias or heading.
+fn resolve_wiki_target(raw: &str) -> Option<String> {
+    let target = raw.split('|').next()?;
+    let target = target.split('#').next()?;
+    let target = target.trim().trim_matches('/');
+    if target.is_empty() {
+        return None;
+    }
+    normalize_path("", target)
+}
+
+/// Resolves a markdown link target relative to the linking note's directory.
+fn resolve_markdown_target(source_path: &str, raw: &str) -> Option<String> {
+    if raw.starts_with('#') || raw.contains("://") || raw.starts_with("mailto:") {
+        return None;
+    }
+
+    let target = raw.split(['#', '?']).next()?;
+    let target = target.replace("%20", " ");
+    let target = target
+        .strip_suffix("_index.md")
+        .or_else(|| target.strip_suffix(".md"))
+        .unwrap_or(&target);
+
+    if target.is_empty() {
+        return None;
+    }
+
+    let resolved = match target.strip_prefix('/') {
+        Some(absolute) => normalize_path("", absolute)?,
+        None => normalize_path(source_path, target)?,
+    };
+
+    // Attachments are files, not notes
+    if resolved.split('/').any(|segment| segment == "_attachments") {
+        return None;
+    }
+
+    Some(resolved)
+}
+
+/// Joins `relative` onto `base` and collapses `.` and `..` segments.
+/// Returns None if the result would escape the notes root.
+fn normalize_path(base: &str, relative: &str) -> Option<String> {
+    let mut segments: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+
+    for segment in relative.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    Some(segments.join("/"))
+}
+
+/// Extracts tags from a note: `#tag` tokens in the body and the `tags` front matter property.
+///
+/// Tags are lowercased and returned without the leading `#`, once each, in order of
+/// first appearance (front matter first). Tags inside code, headings (`# Title`)
+/// and purely numeric tokens (`#1`) are ignored.
+pub fn extract_tags(content: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+
+    let mut push = |tag: &str| {
+        if let Some(tag) = normalize_tag(tag)
+            && !tags.contains(&tag)
+        {
+            tags.push(tag);
+        }
+    };
+
+    if let Some(properties) = parse_front_matter(content)
+        && let Some(value) = properties.get("tags")
+    {
+        for tag in front_matter_tags(value) {
+            push(&tag);
+        }
+    }
+
+    for range in scan_body_tags(content) {
+        push(&content[range]);
+    }
+
+    tags
+}
+
+/// Normalizes a tag for indexing and comparison. Returns None if it is not a valid tag.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#').trim_matches('/');
+    let valid = !tag.is_empty()
+        && tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '/')
+        && !tag.split('/').any(|segment| segment.is_empty())
+        && !tag.chars().all(|c| c.is_ascii_digit());
+
+    valid.then(|| tag.to_lowercase())
+}
+
+/// Renames a tag and its nested tags (`old/child` becomes `new/child`) in a note.
+///
+/// Rewrites `#tag` tokens in the body and items of the `tags` front matter property,
+/// leaving the rest of the note untouched. `old` is matched case-insensitively.
+/// Returns None if the note does not use the tag.
+pub fn rename_tag_in_content(content: &str, old: &str, new: &str) -> Option<String> {
+    let old = normalize_tag(old)?;
+    let rename = |tag: &str| -> Option<String> {
+        let normalized = normalize_tag(tag)?;
+        let rest = normalized.strip_prefix(&old)?;
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+        // Keep the original spelling of nested segments
+        let depth = old.split('/').count();
+        let nested: Vec<&str> = tag.trim_start_matches('#').split('/').skip(depth).collect();
+        let prefix = if tag.starts_with('#') { "#" } else { "" };
+        if nested.is_empty() {
+            Some(format!("{}{}", prefix, new))
+        } else {
+            Some(format!("{}{}/{}", prefix, new, nested.join("/")))
+        }
+    };
+
+    let mut updated = String::with_capacity(content.len());
+    let mut last = 0;
+    let mut changed = false;
+
+    for range in scan_body_tags(content) {
+        if let Some(renamed) = rename(&content[range.clone()]) {
+            updated.push_str(&content[last..range.start]);
+            updated.push_str(&renamed);
+            last = range.end;
+            changed = true;
+        }
+    }
+    updated.push_str(&content[last..]);
+
+    if let Some(properties) = parse_front_matter(&updated)
+        && let Some(value) = properties.get("tags")
+    {
+        let renamed = match value {
+            PropertyValue::List(items) => PropertyValue::List(
+                items
+                    .iter()
+                    .map(|item| rename(item).unwrap_or_else(|| item.clone()))
+                    .collect(),
+            ),
+            PropertyValue::Text(text) => PropertyValue::Text(
+                text.split(',')
+                    .map(|part| {
+                        part.split_whitespace()
+                            .map(|item| rename(item).unwrap_or_else(|| item.to_string()))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        };
+        if &renamed != value {
+            updated = set_front_matter_property(&updated, "tags", &renamed);
+            changed = true;
+        }
+    }
+
+    changed.then_some(updated)
+}
+
+/// Splits the `tags` front matter property into individual tags.
+/// Text values may separate tags with commas or whitespace.
+fn front_matter_tags(value: &PropertyValue) -> Vec<String> {
+    match value {
+        PropertyValue::List(items) => items.clone(),
+        PropertyValue::Text(text) => text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// Finds `#tag` tokens in the body of a note, outside of code and front matter.
+/// Returns the byte range of each tag including its `#`.
+fn scan_body_tags(content: &str) -> Vec<Range<usize>> {
+    let body_start = content.len() - strip_front_matter(content).len();
+    let masked = mask_code(content);
+
+    TAG_REGEX
+        .captures_iter(&masked[body_start..])
+        .filter_map(|cap| {
+            let tag = cap.get(1).unwrap();
+            let range = body_start + tag.start() - 1..body_start + tag.end();
+            normalize_tag(&content[range.clone()]).map(|_| range)
+        })
+        .collect()
+}
+
+/// Replaces fenced code blocks and inline code spans with spaces.
+///
+/// Byte offsets are preserved, so matches found in the masked text can be
+/// sliced out of the original content.
+pub(crate) fn mask_code(content: &str) -> String {
+    let mut masked = String::with_capacity(content.len());
+    let mut fence: Option<&str> = None;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        let marker = ["```", "~~~"]
+            .into_iter()
+            .find(|m| indent <= 3 && trimmed.starts_with(m));
+
+        match (fence, marker) {
+            (None, Some(m)) => {
+                fence = Some(m);
+                masked.push_str(&blank(line));
+            }
+            (Some(open), Some(m)) if open == m => {
+                fence = None;
+                masked.push_str(&blank(line));
+            }
+            (Some(_), _) => masked.push_str(&blank(line)),
+            (None, None) => masked.push_str(&mask_code_spans(line)),
+        }
+    }
+
+    masked
+}
+
+/// Masks backtick code spans within a single line.
+fn mask_code_spans(line: &str) -> String {
+    let bytes = line.as_bytes();
+    let mut masked = line.to_string().into_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'`' {
+            i += 1;
+            continue;
+        }
+
+        let run_start = i;
+        while i < bytes.len() && bytes[i] == b'`' {
+            i += 1;
+        }
+        let run_len = i - run_start;
+
+        // Find the closing run of exactly the same length
+        let mut j = i;
+        let mut close = None;
+        while j < bytes.len() {
+            if bytes[j] == b'`' {
+                let start = j;
+                while j < bytes.len() && bytes[j] == b'`' {
+                    j += 1;
+                }
+                if j - start == run_len {
+                    close = Some(j);
+                    break;
+                }
+            } else {
+                j += 1;
+            }
+        }
+
+        if let Some(end) = close {
+            for b in &mut masked[run_start..end] {
+                *b = b' ';
+            }
+            i = end;
+        }
+    }
+
+    // Spans start and end on backticks, so whole characters are blanked
+    String::from_utf8(masked).unwrap()
+}
+
+fn blank(line: &str) -> String {
+    line.bytes()
+        .map(|b| if b == b'\n' { '\n' } else { ' ' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(source: &str, content: &str) -> Vec<String> {
+        extract_links(source, content)
+            .into_iter()
+            .map(|l| l.target)
+            .collect()
+    }
+
+    #[test]
+    fn test_wiki_links() {
+        let content = "See [[projects/rust]] and [[inbox|my inbox]] or [[journal#today]].";
+        assert_eq!(
+            targets("notes", content),
+            vec!["projects/rust", "inbox", "journal"]
+        );
+    }
+
+    #[test]
+    fn test_markdown_links_are_relative_to_note() {
+        let content =
+            "[child](child) [sibling](../sibling) [root](/inbox) [index](../other/_index.md)";
+        assert_eq!(
+            targets("projects/rust", content),
+            vec![
+                "projects/rust/child",
+                "projects/sibling",
+                "inbox",
+                "projects/other"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignores_external_links_images_and_attachments() {
+        let content = "[site](https://example.com) [mail](mailto:a@b.c) [anchor](#top)\n\
+                       ![img](_attachments/a.png) [file](_attachments/doc.pdf)";
+        assert!(extract_links("note", content).is_empty());
+    }
+
+    #[test]
+    fn test_ignores_links_in_code() {
+        let content = "```\n[[in-fence]]\n```\nInline `[[in-span]]` and [[real]]";
+        assert_eq!(targets("", content), vec!["real"]);
+    }
+
+    #[test]
+    fn test_links_escaping_root_are_dropped() {
+        assert!(extract_links("note", "[up](../../outside)").is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_links_reported_once() {
+        let links = extract_links("", "[[a]] [[a]] [a](a)");
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].kind, LinkKind::Wiki);
+        assert_eq!(links[1].kind, LinkKind::Markdown);
+    }
+
+    fn move_prefix(path: &str, old: &str, new: &str) -> String {
+        match path.strip_prefix(old) {
+            Some("") => new.to_string(),
+            Some(rest) if rest.starts_with('/') => format!("{}{}", new, rest),
+            _ => path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rewrite_wiki_links_keeps_alias_and_heading() {
+        let content = "[[old|Alias]] [[old/child#Top]] [[older]] `[[old]]`";
+        let rewritten =
+            rewrite_links("inbox", "inbox", content, |t| move_prefix(t, "old", "new")).unwrap();
+        assert_eq!(
+            rewritten,
+            "[[new|Alias]] [[new/child#Top]] [[older]] `[[old]]`"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_markdown_links_stay_relative() {
+        let content = "[a](../old) [b](/old/child) [c](../old/_index.md#x)";
+        let rewritten = rewrite_links("projects/inbox", "projects/inbox", content, |t| {
+            move_prefix(t, "projects/old", "archive/new")
+        })
+        .unwrap();
+        assert_eq!(
+            rewritten,
+            "[a](../../archive/new) [b](/old/child) [c](../../archive/new/_index.md#x)"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_links_of_moved_source() {
+        // A note moving deeper keeps its relative links pointing at the same notes
+        let content = "[sibling](../sibling) [child](child)";
+        let rewritten = rewrite_links("a/note", "a/_archive/note", content, |t| {
+            move_prefix(t, "a/note", "a/_archive/note")
+        })
+        .unwrap();
+        assert_eq!(rewritten, "[sibling](../../sibling) [child](child)");
+    }
+
+    #[test]
+    fn test_rewrite_links_unchanged() {
+        let content = "[[other]] [x](../other)";
+        assert!(rewrite_links("a", "a", content, |t| move_prefix(t, "b", "c")).is_none());
+    }
+
+    #[test]
+    fn test_extract_tags() {
+        let content = "---\ntags: [Work, '#meetings']\n---\n\
+                       # Heading\n#todo and #project/alpha,#list [#bracket] #123 a#b\n\
+                       [link](#anchor) `#code`\n```\n#fenced\n```\n#work";
+        assert_eq!(
+            extract_tags(content),
+            vec![
+                "work",
+                "meetings",
+                "todo",
+                "project/alpha",
+                "list",
+                "bracket"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_front_matter_text_tags() {
+        assert_eq!(
+            extract_tags("---\ntags: a, b c\n---\n"),
+            vec!["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(
+            normalize_tag("#Work/Meetings/"),
+            Some("work/meetings".to_string())
+        );
+        assert_eq!(normalize_tag("42"), None);
+        assert_eq!(normalize_tag("a//b"), None);
+        assert_eq!(normalize_tag("has space"), None);
+    }
+
+    #[test]
+    fn test_rename_tag_in_content() {
+        let content =
+            "---\ntags: [work, other]\n---\n#Work and #work/Meetings, not #workshop `#work`";
+        assert_eq!(
+            rename_tag_in_content(content, "work", "job").unwrap(),
+            "---\ntags: [job, other]\n---\n#job and #job/Meetings, not #workshop `#work`"
+        );
+        assert!(rename_tag_in_content(content, "missing", "x").is_none());
+    }
+
+    #[test]
+    fn test_mask_code_preserves_offsets() {
+        let content = "héllo `cödé` wörld\n```\nfenced\n```\n";
+        let masked = mask_code(content);
+        assert_eq!(masked.len(), content.len());
+        assert!(!masked.contains("cödé"));
+        assert!(!masked.contains("fenced"));
+        assert!(masked.contains("wörld"));
+    }
+}
@@ -0,0 +1,229 @@
+use crate::diff::matching_lines;
+
+/// How [`NotesApi::merge_note`](crate::NotesApi::merge_note) handles conflicting edits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Leave the note untouched and return the conflicting regions
+    Structured,
+    /// Save the note with git-style conflict markers around conflicting regions
+    ConflictMarkers,
+}
+
+/// A region of a three-way merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeRegion {
+    /// Lines both sides agree on, or that only one side changed
+    Resolved(Vec<String>),
+    /// Lines both sides changed differently
+    Conflict {
+        base: Vec<String>,
+        ours: Vec<String>,
+        theirs: Vec<String>,
+    },
+}
+
+/// The result of merging a note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeOutcome {
+    /// Both edits merged cleanly; the merged content was saved
+    Clean(String),
+    /// The edits conflict; nothing was saved (`MergeStrategy::Structured`)
+    Conflicts(Vec<MergeRegion>),
+    /// The edits conflict; the content was saved with conflict markers
+    /// (`MergeStrategy::ConflictMarkers`)
+    Marked(String),
+}
+
+/// Merges the changes from `base` to `ours` with the changes from `base` to `theirs`.
+///
+/// A line-based diff3: regions where both sides match the base are kept, regions
+/// changed on one side take that side, and regions changed identically on both
+/// sides take either. Anything else is a conflict.
+pub fn merge3(base: &str, ours: &str, theirs: &str) -> Vec<MergeRegion> {
+    let base_lines: Vec<&str> = base.lines().collect();
+    let our_lines: Vec<&str> = ours.lines().collect();
+    let their_lines: Vec<&str> = theirs.lines().collect();
+
+    // For each base line, its index in ours/theirs if a minimal diff keeps it
+    let mut in_ours = vec![None; base_lines.len()];
+    for (b, o) in matching_lines(&base_lines, &our_lines) {
+        in_ours[b] = Some(o);
+    }
+    let mut in_theirs = vec![None; base_lines.len()];
+    for (b, t) in matching_lines(&base_lines, &their_lines) {
+        in_theirs[b] = Some(t);
+    }
+
+    let mut regions = Vec::new();
+    let (mut b, mut o, mut t) = (0, 0, 0);
+
+    loop {
+        // Lines unchanged on both sides
+        let mut stable = 0;
+        while b + stable < base_lines.len()
+            && in_ours[b + stable] == Some(o + stable)
+            && in_theirs[b + stable] == Some(t + stable)
+        {
+            stable += 1;
+        }
+        if stable > 0 {
+            push_resolved(&mut regions, &base_lines[b..b + stable]);
+            b += stable;
+            o += stable;
+            t += stable;
+            continue;
+        }
+
+        // Everything up to the next base line both sides kept was changed by at least one
+        let next = (b..base_lines.len())
+            .find_map(|i| Some((i, in_ours[i]?, in_theirs[i]?)))
+            .unwrap_or((base_lines.len(), our_lines.len(), their_lines.len()));
+
+        let (base_chunk, ours_chunk, theirs_chunk) = (
+            &base_lines[b..next.0],
+            &our_lines[o..next.1],
+            &their_lines[t..next.2],
+        );
+        if ours_chunk == base_chunk || ours_chunk == theirs_chunk {
+            push_resolved(&mut regions, theirs_chunk);
+        } else if theirs_chunk == base_chunk {
+            push_resolved(&mut regions, ours_chunk);
+        } else {
+            regions.push(MergeRegion::Conflict {
+                base: to_strings(base_chunk),
+                ours: to_strings(ours_chunk),
+                theirs: to_strings(theirs_chunk),
+            });
+        }
+
+        (b, o, t) = next;
+        if b == base_lines.len() && o == our_lines.len() && t == their_lines.len() {
+            break;
+        }
+    }
+
+    regions
+}
+
+/// Whether the merged text should end with a newline: whichever side changed
+/// the base's trailing newline wins.
+pub(crate) fn merged_trailing_newline(base: &str, ours: &str, theirs: &str) -> bool {
+    if ours.ends_with('\n') != base.ends_with('\n') {
+        ours.ends_with('\n')
+    } else {
+        theirs.ends_with('\n')
+    }
+}
+
+/// Renders merge regions as text, marking conflicts git-style.
+pub(crate) fn render_merge(regions: &[MergeRegion], trailing_newline: bool) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for region in regions {
+        match region {
+            MergeRegion::Resolved(resolved) => lines.extend(resolved.iter().map(String::as_str)),
+            MergeRegion::Conflict { ours, theirs, .. } => {
+                lines.push("<<<<<<< ours");
+                lines.extend(ours.iter().map(String::as_str));
+                lines.push("=======");
+                lines.extend(theirs.iter().map(String::as_str));
+                lines.push(">>>>>>> theirs");
+            }
+        }
+    }
+
+    let mut text = lines.join("\n");
+    if trailing_newline && !text.is_empty() {
+        text.push('\n');
+    }
+    text
+}
+
+/// Appends lines to the last region if it's resolved too, so resolved text is contiguous.
+fn push_resolved(regions: &mut Vec<MergeRegion>, lines: &[&str]) {
+    if lines.is_empty() {
+        return;
+    }
+    match regions.last_mut() {
+        Some(MergeRegion::Resolved(resolved)) => {
+            resolved.extend(lines.iter().map(|l| l.to_string()))
+        }
+        _ => regions.push(MergeRegion::Resolved(to_strings(lines))),
+    }
+}
+
+fn to_strings(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|l| l.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(base: &str, ours: &str, theirs: &str) -> String {
+        render_merge(
+            &merge3(base, ours, theirs),
+            merged_trailing_newline(base, ours, theirs),
+        )
+    }
+
+    #[test]
+    fn test_merge_disjoint_edits() {
+        let base = "one\ntwo\nthree\nfour\n";
+        let ours = "ONE\ntwo\nthree\nfour\n";
+        let theirs = "one\ntwo\nthree\nFOUR\nfive\n";
+
+        let regions = merge3(base, ours, theirs);
+        assert!(
+            regions
+                .iter()
+                .all(|r| matches!(r, MergeRegion::Resolved(_)))
+        );
+        assert_eq!(merged(base, ours, theirs), "ONE\ntwo\nthree\nFOUR\nfive\n");
+    }
+
+    #[test]
+    fn test_merge_same_edit_on_both_sides() {
+        let base = "a\nb\nc";
+        assert_eq!(merged(base, "a\nB\nc", "a\nB\nc"), "a\nB\nc");
+        // Deletions on either side are kept
+        assert_eq!(merged(base, "a\nc", "a\nb\nc\nd"), "a\nc\nd");
+    }
+
+    #[test]
+    fn test_merge_conflict() {
+        let base = "title\nbody\nend";
+        let ours = "title\nour body\nend";
+        let theirs = "title\ntheir body\nend";
+
+        assert_eq!(
+            merge3(base, ours, theirs),
+            vec![
+                MergeRegion::Resolved(vec!["title".to_string()]),
+                MergeRegion::Conflict {
+                    base: vec!["body".to_string()],
+                    ours: vec!["our body".to_string()],
+                    theirs: vec!["their body".to_string()],
+                },
+                MergeRegion::Resolved(vec!["end".to_string()]),
+            ]
+        );
+        assert_eq!(
+            merged(base, ours, theirs),
+            "title\n<<<<<<< ours\nour body\n=======\ntheir body\n>>>>>>> theirs\nend"
+        );
+    }
+
+    #[test]
+    fn test_merge_both_append() {
+        // Both sides adding at the end of the same base conflicts
+        let regions = merge3("a\n", "a\nours\n", "a\ntheirs\n");
+        assert_eq!(regions.len(), 2);
+        assert!(matches!(
+            &regions[1],
+            MergeRegion::Conflict { base, .. } if base.is_empty()
+        ));
+
+        assert_eq!(merged("", "", ""), "");
+        assert_eq!(merged("", "new\n", ""), "new\n");
+    }
+}
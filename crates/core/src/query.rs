@@ -0,0 +1,610 @@
+use rusqlite::types::Value;
+
+use crate::markdown::normalize_tag;
+use crate::notes::{Error, Result};
+
+/// A parsed note query.
+///
+/// Produced by [`parse_query`] from queries such as
+/// `tag:work path:projects/ modified:>2026-01-01 archived:false "exact phrase"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// A bare word, matched against note content
+    Text(String),
+    /// A quoted phrase, matched against note content
+    Phrase(String),
+    /// `tag:name`, matching the tag and tags nested under it
+    Tag(String),
+    /// `path:prefix`, matching note paths starting with the prefix
+    Path(String),
+    /// `modified:>2026-01-01`, comparing the modification date (UTC) to a day
+    Modified(Comparison, Date),
+    /// `archived:true` or `archived:false`
+    Archived(bool),
+    /// `key:value` for any other key, matching a front matter property
+    Property { key: String, value: String },
+    /// `-term`
+    Not(Box<Query>),
+    /// Terms separated by whitespace. An empty And matches every note.
+    And(Vec<Query>),
+    /// Terms separated by `OR`
+    Or(Vec<Query>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Before,
+    BeforeOrOn,
+    On,
+    OnOrAfter,
+    After,
+}
+
+/// A calendar day, as written in queries (`YYYY-MM-DD`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// Unix timestamp of the start of the day in UTC.
+    fn unix_start(&self) -> i64 {
+        // Days from civil algorithm (proleptic Gregorian calendar)
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        (era * 146097 + day_of_era - 719468) * 86400
+    }
+}
+
+impl Query {
+    /// Returns true if the query filters on the archived flag anywhere.
+    fn mentions_archived(&self) -> bool {
+        match self {
+            Query::Archived(_) => true,
+            Query::Not(inner) => inner.mentions_archived(),
+            Query::And(terms) | Query::Or(terms) => terms.iter().any(Query::mentions_archived),
+            _ => false,
+        }
+    }
+
+    /// Compiles the query to a SQL condition over the `notes` table,
+    /// appending its bound values to `params`.
+    ///
+    /// Archived notes are excluded unless the query filters on `archived:`.
+    pub(crate) fn to_sql(&self, params: &mut Vec<Value>) -> String {
+        let condition = self.condition(params);
+        if self.mentions_archived() {
+            condition
+        } else {
+            format!("notes.archived = 0 AND ({})", condition)
+        }
+    }
+
+    fn condition(&self, params: &mut Vec<Value>) -> String {
+        match self {
+            Query::Text(text) | Query::Phrase(text) => {
+                params.push(Value::Text(fts_quote(text)));
+                "notes.id IN (SELECT rowid FROM notes_fts WHERE notes_fts MATCH ?)".to_string()
+            }
+            Query::Tag(tag) => {
+                let nested = format!("{}/", tag);
+                params.push(Value::Text(tag.clone()));
+                params.push(Value::Text(nested.clone()));
+                params.push(Value::Text(nested));
+                "notes.id IN (SELECT note_id FROM note_tags
+                              WHERE tag = ? OR substr(tag, 1, length(?)) = ?)"
+                    .to_string()
+            }
+            Query::Path(prefix) => {
+                let escaped = prefix
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                params.push(Value::Text(format!("{}%", escaped)));
+                "notes.path LIKE ? ESCAPE '\\'".to_string()
+            }
+            Query::Modified(comparison, date) => {
+                let start = date.unix_start();
+                let end = start + 86400;
+                match comparison {
+                    Comparison::Before => {
+                        params.push(Value::Integer(start));
+                        "notes.mtime < ?".to_string()
+                    }
+                    Comparison::BeforeOrOn => {
+                        params.push(Value::Integer(end));
+                        "notes.mtime < ?".to_string()
+                    }
+                    Comparison::On => {
+                        params.push(Value::Integer(start));
+                        params.push(Value::Integer(end));
+                        "(notes.mtime >= ? AND notes.mtime < ?)".to_string()
+                    }
+                    Comparison::OnOrAfter => {
+                        params.push(Value::Integer(start));
+                        "notes.mtime >= ?".to_string()
+                    }
+                    Comparison::After => {
+                        params.push(Value::Integer(end));
+                        "notes.mtime >= ?".to_string()
+                    }
+                }
+            }
+            Query::Archived(archived) => {
+                format!("notes.archived = {}", i64::from(*archived))
+            }
+            Query::Property { key, value } => {
+                params.push(Value::Text(key.clone()));
+                params.push(Value::Text(value.clone()));
+                "notes.id IN (SELECT note_id FROM note_properties
+                              WHERE key = ? AND value = ? COLLATE NOCASE)"
+                    .to_string()
+            }
+            Query::Not(inner) => format!("NOT ({})", inner.condition(params)),
+            Query::And(terms) if terms.is_empty() => "1".to_string(),
+            Query::And(terms) => join(terms, " AND ", params),
+            Query::Or(terms) => join(terms, " OR ", params),
+        }
+    }
+}
+
+/// Quotes text as an FTS5 string, so punctuation and operators are matched literally.
+fn fts_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Turns free text into a safe FTS5 query.
+///
+/// Each whitespace-separated word becomes a quoted string that must appear in the
+/// note, so FTS5 syntax in the input is matched literally instead of being parsed.
+/// Returns None if the input contains nothing searchable.
+pub(crate) fn fts_text_query(input: &str) -> Option<String> {
+    let words: Vec<String> = input
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(fts_quote)
+        .collect();
+
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+fn join(terms: &[Query], separator: &str, params: &mut Vec<Value>) -> String {
+    terms
+        .iter()
+        .map(|term| format!("({})", term.condition(params)))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Open,
+    Close,
+    Or,
+    Not,
+    Phrase(String),
+    /// A bare word, or `field:value` if it has a field
+    Word {
+        field: Option<String>,
+        value: String,
+        /// Byte offset of the value within the query
+        value_position: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+fn invalid(position: usize, message: impl Into<String>) -> Error {
+    Error::InvalidQuery {
+        position,
+        message: message.into(),
+    }
+}
+
+/// Parses a note query.
+///
+/// Terms separated by whitespace must all match; `OR` between terms matches
+/// either side and binds looser than whitespace. `-term` negates a term and
+/// parentheses group terms. Supported fields are `tag:`, `path:`, `modified:`
+/// (`YYYY-MM-DD`, optionally prefixed with `<`, `<=`, `>` or `>=`) and
+/// `archived:`; any other `key:value` matches a front matter property. Values
+/// containing spaces can be quoted (`path:"my notes/"`).
+///
+/// Returns `Error::InvalidQuery` with the byte offset of the problem if the
+/// query cannot be parsed.
+pub fn parse_query(input: &str) -> Result<Query> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        end: input.len(),
+    };
+
+    let query = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        // The only token that can stop parse_or early is an unmatched ')'
+        return Err(invalid(token.position, "unmatched ')'"));
+    }
+
+    Ok(query)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token {
+                    kind: TokenKind::Open,
+                    position,
+                });
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token {
+                    kind: TokenKind::Close,
+                    position,
+                });
+            }
+            '"' => {
+                chars.next();
+                let text = read_quoted(position, &mut chars)?;
+                tokens.push(Token {
+                    kind: TokenKind::Phrase(text),
+                    position,
+                });
+            }
+            '-' => {
+                chars.next();
+                match chars.peek() {
+                    Some(&(_, next)) if !next.is_whitespace() && next != ')' => {
+                        tokens.push(Token {
+                            kind: TokenKind::Not,
+                            position,
+                        });
+                    }
+                    _ => return Err(invalid(position, "expected a term after '-'")),
+                }
+            }
+            _ => {
+                let mut end = position;
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let word = &input[position..end];
+
+                if word == "OR" {
+                    tokens.push(Token {
+                        kind: TokenKind::Or,
+                        position,
+                    });
+                    continue;
+                }
+                if word == "AND" {
+                    // Terms are combined with AND by default
+                    continue;
+                }
+
+                let kind = match word.split_once(':') {
+                    Some((field, value)) if !field.is_empty() => {
+                        let value_position = position + field.len() + 1;
+                        let value = if value.is_empty() && matches!(chars.peek(), Some(&(_, '"'))) {
+                            chars.next();
+                            read_quoted(value_position, &mut chars)?
+                        } else {
+                            value.to_string()
+                        };
+                        if value.is_empty() {
+                            return Err(invalid(
+                                value_position,
+                                format!("expected a value after '{}:'", field),
+                            ));
+                        }
+                        TokenKind::Word {
+                            field: Some(field.to_lowercase()),
+                            value,
+                            value_position,
+                        }
+                    }
+                    _ => TokenKind::Word {
+                        field: None,
+                        value: word.to_string(),
+                        value_position: position,
+                    },
+                };
+                tokens.push(Token { kind, position });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a quoted string whose opening quote at `start` has been consumed.
+/// A doubled quote (`""`) stands for a literal quote.
+fn read_quoted(
+    start: usize,
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+) -> Result<String> {
+    let mut text = String::new();
+    while let Some((_, c)) = chars.next() {
+        if c == '"' {
+            if matches!(chars.peek(), Some(&(_, '"'))) {
+                chars.next();
+                text.push('"');
+                continue;
+            }
+            return Ok(text);
+        }
+        text.push(c);
+    }
+
+    Err(invalid(start, "unterminated quote"))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    /// Byte length of the query, reported for errors at the end of input
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Query> {
+        let mut terms = vec![self.parse_and()?];
+        while let Some(token) = self.peek() {
+            if token.kind != TokenKind::Or {
+                break;
+            }
+            let position = token.position;
+            self.next();
+            let term = self.parse_and()?;
+            if term == Query::And(Vec::new()) {
+                return Err(invalid(position, "expected a term after OR"));
+            }
+            terms.push(term);
+        }
+
+        if terms.len() == 1 {
+            return Ok(terms.pop().unwrap());
+        }
+        if terms[0] == Query::And(Vec::new()) {
+            return Err(invalid(0, "expected a term before OR"));
+        }
+        Ok(Query::Or(terms))
+    }
+
+    fn parse_and(&mut self) -> Result<Query> {
+        let mut terms = Vec::new();
+        while let Some(token) = self.peek() {
+            if matches!(token.kind, TokenKind::Or | TokenKind::Close) {
+                break;
+            }
+            terms.push(self.parse_unary()?);
+        }
+
+        if terms.len() == 1 {
+            Ok(terms.pop().unwrap())
+        } else {
+            Ok(Query::And(terms))
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Query> {
+        let token = self
+            .next()
+            .ok_or_else(|| invalid(self.end, "expected a term"))?;
+
+        match token.kind {
+            TokenKind::Not => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            TokenKind::Open => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::Close,
+                        ..
+                    }) => {}
+                    _ => return Err(invalid(token.position, "unmatched '('")),
+                }
+                if inner == Query::And(Vec::new()) {
+                    return Err(invalid(token.position, "empty parentheses"));
+                }
+                Ok(inner)
+            }
+            TokenKind::Phrase(text) => Ok(Query::Phrase(text)),
+            TokenKind::Word {
+                field,
+                value,
+                value_position,
+            } => parse_term(field, value, value_position),
+            TokenKind::Close | TokenKind::Or => Err(invalid(token.position, "expected a term")),
+        }
+    }
+}
+
+fn parse_term(field: Option<String>, value: String, position: usize) -> Result<Query> {
+    let Some(field) = field else {
+        return Ok(Query::Text(value));
+    };
+
+    match field.as_str() {
+        "tag" => normalize_tag(&value)
+            .map(Query::Tag)
+            .ok_or_else(|| invalid(position, format!("invalid tag '{}'", value))),
+        "path" => Ok(Query::Path(value)),
+        "modified" => parse_date_comparison(&value, position),
+        "archived" => match value.to_lowercase().as_str() {
+            "true" | "yes" => Ok(Query::Archived(true)),
+            "false" | "no" => Ok(Query::Archived(false)),
+            _ => Err(invalid(position, "expected true or false")),
+        },
+        _ => Ok(Query::Property { key: field, value }),
+    }
+}
+
+fn parse_date_comparison(value: &str, position: usize) -> Result<Query> {
+    let (comparison, date) = [
+        (">=", Comparison::OnOrAfter),
+        ("<=", Comparison::BeforeOrOn),
+        (">", Comparison::After),
+        ("<", Comparison::Before),
+        ("=", Comparison::On),
+    ]
+    .into_iter()
+    .find_map(|(prefix, comparison)| value.strip_prefix(prefix).map(|rest| (comparison, rest)))
+    .unwrap_or((Comparison::On, value));
+
+    let date_position = position + (value.len() - date.len());
+    parse_date(date)
+        .map(|date| Query::Modified(comparison, date))
+        .ok_or_else(|| invalid(date_position, "expected a date like 2026-01-31"))
+}
+
+fn parse_date(text: &str) -> Option<Date> {
+    let mut parts = text.split('-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+
+    let date = Date {
+        year: year.parse().ok()?,
+        month: month.parse().ok()?,
+        day: day.parse().ok()?,
+    };
+
+    let leap = date.year % 4 == 0 && (date.year % 100 != 0 || date.year % 400 == 0);
+    let days_in_month = match date.month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return None,
+    };
+
+    (1..=days_in_month).contains(&date.day).then_some(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(input: &str) -> usize {
+        match parse_query(input) {
+            Err(Error::InvalidQuery { position, .. }) => position,
+            other => panic!("expected InvalidQuery for {:?}, got {:?}", input, other),
+        }
+    }
+
+    #[test]
+    fn test_parse_fields_and_phrases() {
+        let query = parse_query(
+            "tag:Work path:projects/ modified:>2026-01-01 archived:false \"exact phrase\"",
+        )
+        .unwrap();
+        assert_eq!(
+            query,
+            Query::And(vec![
+                Query::Tag("work".to_string()),
+                Query::Path("projects/".to_string()),
+                Query::Modified(
+                    Comparison::After,
+                    Date {
+                        year: 2026,
+                        month: 1,
+                        day: 1
+                    }
+                ),
+                Query::Archived(false),
+                Query::Phrase("exact phrase".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_boolean_structure() {
+        let query = parse_query("a OR -(b status:\"in progress\") AND c").unwrap();
+        assert_eq!(
+            query,
+            Query::Or(vec![
+                Query::Text("a".to_string()),
+                Query::And(vec![
+                    Query::Not(Box::new(Query::And(vec![
+                        Query::Text("b".to_string()),
+                        Query::Property {
+                            key: "status".to_string(),
+                            value: "in progress".to_string()
+                        },
+                    ]))),
+                    Query::Text("c".to_string()),
+                ]),
+            ])
+        );
+
+        assert_eq!(parse_query("  ").unwrap(), Query::And(Vec::new()));
+    }
+
+    #[test]
+    fn test_invalid_query_positions() {
+        assert_eq!(position("foo \"bar"), 4);
+        assert_eq!(position("(foo"), 0);
+        assert_eq!(position("foo)"), 3);
+        assert_eq!(position("modified:>2026-13-01"), 10);
+        assert_eq!(position("archived:maybe"), 9);
+        assert_eq!(position("tag:"), 4);
+        assert_eq!(position("tag:a//b"), 4);
+        assert_eq!(position("foo OR"), 4);
+        assert_eq!(position("OR foo"), 0);
+        assert_eq!(position("foo - bar"), 4);
+        assert_eq!(position("()"), 0);
+    }
+
+    #[test]
+    fn test_fts_text_query() {
+        assert_eq!(
+            fts_text_query("foo-bar \"baz"),
+            Some("\"foo-bar\" \"\"\"baz\"".to_string())
+        );
+        assert_eq!(fts_text_query("  - * ( "), None);
+    }
+
+    #[test]
+    fn test_date_to_unix() {
+        let date = parse_date("2026-01-01").unwrap();
+        assert_eq!(date.unix_start(), 1767225600);
+        assert_eq!(parse_date("1970-01-01").unwrap().unix_start(), 0);
+        assert!(parse_date("2025-02-29").is_none());
+        assert!(parse_date("2024-02-29").is_some());
+    }
+}
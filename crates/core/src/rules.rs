@@ -0,0 +1,25 @@
+use std::time::{Duration, SystemTime};
+
+/// A rule that archives notes nobody has opened or edited for a while.
+///
+/// Rules are stored in the database and run by
+/// [`NotesApi::apply_rules`](crate::NotesApi::apply_rules).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveRule {
+    pub id: i64,
+    /// The note whose children the rule archives, or None for root-level notes
+    pub parent: Option<String>,
+    /// How long a child must go without being accessed or modified, counting its
+    /// descendants, before it's archived
+    pub inactive_for: Duration,
+}
+
+/// A note an archive rule would archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatch {
+    /// The rule that matched (the oldest, if several did)
+    pub rule_id: i64,
+    pub path: String,
+    /// When the note or one of its descendants was last accessed or modified
+    pub last_active: SystemTime,
+}
@@ -0,0 +1,73 @@
+/// Maximum number of operations kept for undo
+pub(crate) const UNDO_LIMIT: usize = 100;
+
+/// A structural change to the notes tree, kept so it can be undone and redone.
+#[derive(Debug, Clone)]
+pub(crate) enum Operation {
+    /// A note was created. While the creation is undone, `stash` holds the note.
+    Created {
+        path: String,
+        stash: Option<Stash>,
+    },
+    Renamed {
+        old_path: String,
+        new_path: String,
+    },
+    Archived {
+        path: String,
+        archive_path: String,
+    },
+    Unarchived {
+        archive_path: String,
+        path: String,
+    },
+    /// A note and its descendants were deleted, or moved to the system trash.
+    /// Until the removal is undone, `stash` holds the notes.
+    Removed {
+        path: String,
+        trashed: bool,
+        stash: Option<Stash>,
+    },
+}
+
+impl Operation {
+    /// The stash this operation holds, which must be dropped along with it.
+    pub(crate) fn stash(&self) -> Option<&Stash> {
+        match self {
+            Operation::Created { stash, .. } | Operation::Removed { stash, .. } => stash.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Removed notes, with their files in the filesystem stash and their index rows here.
+#[derive(Debug, Clone)]
+pub(crate) struct Stash {
+    pub id: u64,
+    pub rows: Vec<NoteRow>,
+}
+
+/// The parts of a `notes` row that can't be rebuilt from the note's content.
+#[derive(Debug, Clone)]
+pub(crate) struct NoteRow {
+    pub id: i64,
+    pub path: String,
+    pub archived: bool,
+    pub archived_at: Option<i64>,
+    pub access_count: i64,
+    pub last_accessed_at: Option<i64>,
+    pub frecency_score: f64,
+    pub direct_access_count: i64,
+    /// The note's revision history, which is deleted along with the row
+    pub revisions: Vec<RevisionRow>,
+}
+
+/// A `revisions` row, with its content still compressed.
+#[derive(Debug, Clone)]
+pub(crate) struct RevisionRow {
+    pub id: i64,
+    pub created_at: i64,
+    pub content_hash: String,
+    pub size: i64,
+    pub content: Vec<u8>,
+}
@@ -1,16 +1,38 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
 
 use crate::NotesApi;
+use crate::filesystem::STASH_DIR;
+
+/// How long the filesystem must be quiet before queued changes are applied
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Longest a change waits in the queue while changes keep coming in
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(1);
 
 /// Event type emitted by the filesystem watcher
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum WatcherEvent {
-    /// Notes were modified (created, updated, or deleted)
-    NotesChanged,
-    /// Notes were renamed or moved
-    NotesRenamed,
+    /// A note was created
+    Created { path: String },
+    /// A note's content changed, from content hash `old_hash` to `new_hash`
+    Modified {
+        path: String,
+        old_hash: String,
+        new_hash: String,
+    },
+    /// A note was deleted
+    Deleted { path: String },
+    /// A note was renamed or moved
+    Moved { from: String, to: String },
+    /// A note at `path` was moved into an `_archive` folder
+    Archived { path: String },
     /// Frecency scores were updated (navigation should refresh)
     FrecencyUpdated,
 }
@@ -23,12 +45,17 @@ pub enum WatcherEvent {
 /// - Note folder creation and deletion
 /// - Note folder renames and moves
 ///
-/// The watcher uses debouncing to avoid excessive rescans during bulk operations.
+/// Changes are queued and applied in batches once the filesystem has been quiet
+/// for a moment, so bulk changes like a `git pull` are synced in one transaction
+/// and reported with a single callback listing what happened to each note. A note
+/// that disappears and one that appears with the same content within a batch are
+/// taken to be a move, so the note keeps its id, frecency, revisions and tags.
 ///
 /// # Arguments
 ///
 /// * `notes_api` - Arc-wrapped NotesApi instance to sync when changes are detected
-/// * `on_change` - Optional callback function that will be called when changes are detected
+/// * `on_change` - Optional callback function that will be called with the events for
+///   each batch of changes
 ///
 /// # Returns
 ///
@@ -43,19 +70,24 @@ pub enum WatcherEvent {
 ///
 /// let api = NotesApi::new("/path/to/notes").unwrap();
 /// let api = Arc::new(Mutex::new(api));
-/// let _watcher = setup_watcher(Arc::clone(&api), None::<fn(WatcherEvent)>);
+/// let _watcher = setup_watcher(Arc::clone(&api), None::<fn(Vec<WatcherEvent>)>);
 /// // Keep _watcher alive while you want to monitor filesystem changes
 /// ```
 pub fn setup_watcher<F>(notes_api: Arc<Mutex<NotesApi>>, on_change: Option<F>) -> RecommendedWatcher
 where
-    F: Fn(WatcherEvent) + Send + 'static,
+    F: Fn(Vec<WatcherEvent>) + Send + 'static,
 {
-    let notes_root = {
+    let (notes_root, operation_flag) = {
         let api = notes_api.lock().unwrap();
-        api.notes_root().to_path_buf()
+        (api.notes_root().to_path_buf(), api.operation_flag())
     };
 
+    // Changes are applied on a separate thread, which stops when the watcher is dropped
+    let (queue, changes) = mpsc::channel();
+    thread::spawn(move || apply_changes(changes, notes_api, on_change));
+
     let notes_root_clone = notes_root.clone();
+    let notes_root_filter = notes_root.clone();
 
     // Helper function to convert filesystem path to note path
     let path_to_note_path = move |fs_path: &std::path::Path| -> Option<String> {
@@ -93,6 +125,16 @@ where
                         return;
                     }
 
+                    // Ignore notes moving in and out of the undo stash
+                    let is_stash_change = event.paths.iter().any(|p| {
+                        p.strip_prefix(&notes_root_filter)
+                            .is_ok_and(|relative| relative.starts_with(STASH_DIR))
+                    });
+
+                    if is_stash_change {
+                        return;
+                    }
+
                     // Check if this is a note-related change (involves _index.md or note directories)
                     let is_note_related = event.paths.iter().any(|p| {
                         // Check if it's an _index.md file
@@ -114,49 +156,33 @@ where
                         return;
                     }
 
+                    // Atomic saves rename a temp file over _index.md; that's a content
+                    // change, not a moved note
+                    let is_index_replaced = event.paths.iter().all(|p| !p.is_dir())
+                        && event.paths.iter().any(|p| {
+                            p.file_name().and_then(|n| n.to_str()) == Some("_index.md")
+                                && p.is_file()
+                        });
+
                     use notify::EventKind;
                     match event.kind {
                         // Handle rename/move events - need full rescan
-                        EventKind::Modify(notify::event::ModifyKind::Name(_)) => {
-                            if let Ok(mut api) = notes_api.lock() {
-                                if let Err(e) = api.rescan() {
-                                    eprintln!("Failed to rescan after rename: {:?}", e);
-                                } else if let Some(ref callback) = on_change {
-                                    callback(WatcherEvent::NotesRenamed);
-                                }
-                            }
+                        EventKind::Modify(notify::event::ModifyKind::Name(_))
+                            if !is_index_replaced =>
+                        {
+                            queue.send(Change::Rescan).ok();
                         }
                         // Handle create, modify, and delete events for specific notes
                         EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
+                            // Skip if an operation is in progress (API is making changes)
+                            if operation_flag.load(Ordering::SeqCst) {
+                                return;
+                            }
+
                             // Extract note paths from the event
                             for path in &event.paths {
-                                // Convert filesystem path to note path
-                                if let Some(note_path) = path_to_note_path(path)
-                                    && let Ok(mut api) = notes_api.lock()
-                                {
-                                    // Skip if an operation is in progress (API is making changes)
-                                    if api
-                                        .operation_flag()
-                                        .load(std::sync::atomic::Ordering::SeqCst)
-                                    {
-                                        continue;
-                                    }
-
-                                    // Use sync_note which returns true only if content changed
-                                    match api.sync_note(&note_path) {
-                                        Ok(true) => {
-                                            // Only notify if content actually changed
-                                            if let Some(ref callback) = on_change {
-                                                callback(WatcherEvent::NotesChanged);
-                                            }
-                                        }
-                                        Ok(false) => {
-                                            // Don't notify - content is identical
-                                        }
-                                        Err(e) => {
-                                            eprintln!("Failed to sync note {}: {:?}", note_path, e);
-                                        }
-                                    }
+                                if let Some(note_path) = path_to_note_path(path) {
+                                    queue.send(Change::Note(note_path)).ok();
                                 }
                             }
                         }
@@ -178,3 +204,174 @@ where
 
     watcher
 }
+
+/// A change reported by the filesystem, waiting to be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Change {
+    /// A note's content or existence changed
+    Note(String),
+    /// Notes were renamed or moved, so the whole tree needs rescanning
+    Rescan,
+}
+
+/// Changes coalesced into one batch.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Batch {
+    paths: BTreeSet<String>,
+    rescan: bool,
+}
+
+impl Batch {
+    fn add(&mut self, change: Change) {
+        match change {
+            Change::Note(path) => {
+                self.paths.insert(path);
+            }
+            Change::Rescan => self.rescan = true,
+        }
+    }
+}
+
+/// Applies queued changes in batches until the queue is closed.
+fn apply_changes<F>(
+    changes: Receiver<Change>,
+    notes_api: Arc<Mutex<NotesApi>>,
+    on_change: Option<F>,
+) where
+    F: Fn(Vec<WatcherEvent>),
+{
+    while let Ok(first) = changes.recv() {
+        let (batch, closed) = collect_batch(&changes, first, DEBOUNCE, MAX_BATCH_DELAY);
+        apply_batch(batch, &notes_api, on_change.as_ref());
+        if closed {
+            break;
+        }
+    }
+}
+
+/// Collects changes following `first` until none arrive for `debounce`, or `max_delay`
+/// has passed. Also returns whether the queue was closed.
+fn collect_batch(
+    changes: &Receiver<Change>,
+    first: Change,
+    debounce: Duration,
+    max_delay: Duration,
+) -> (Batch, bool) {
+    let mut batch = Batch::default();
+    batch.add(first);
+
+    let deadline = Instant::now() + max_delay;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return (batch, false);
+        }
+        match changes.recv_timeout(debounce.min(remaining)) {
+            Ok(change) => batch.add(change),
+            Err(RecvTimeoutError::Timeout) => return (batch, false),
+            Err(RecvTimeoutError::Disconnected) => return (batch, true),
+        }
+    }
+}
+
+/// Syncs a batch of changes with the database, holding the API lock once for the
+/// whole batch, and reports the batch's events in a single callback.
+fn apply_batch<F>(batch: Batch, notes_api: &Mutex<NotesApi>, on_change: Option<&F>)
+where
+    F: Fn(Vec<WatcherEvent>),
+{
+    let Ok(mut api) = notes_api.lock() else {
+        return;
+    };
+
+    // A rescan picks up every other change too
+    let events = if batch.rescan {
+        match api.rescan() {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("Failed to rescan after rename: {:?}", e);
+                return;
+            }
+        }
+    } else {
+        let paths: Vec<String> = batch.paths.into_iter().collect();
+        match api.sync_notes(&paths) {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("Failed to sync {} notes: {:?}", paths.len(), e);
+                return;
+            }
+        }
+    };
+
+    // Only notify if content actually changed
+    if !events.is_empty()
+        && let Some(callback) = on_change
+    {
+        callback(events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_batch_coalesces_changes() {
+        let (queue, changes) = mpsc::channel();
+        for path in ["a", "b", "a", "c/d"] {
+            queue.send(Change::Note(path.to_string())).unwrap();
+        }
+
+        let (batch, closed) = collect_batch(
+            &changes,
+            Change::Note("b".to_string()),
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+        );
+        assert!(!closed);
+        assert!(!batch.rescan);
+        assert_eq!(
+            batch.paths.into_iter().collect::<Vec<_>>(),
+            vec!["a", "b", "c/d"]
+        );
+
+        queue.send(Change::Rescan).unwrap();
+        drop(queue);
+        let (batch, closed) = collect_batch(
+            &changes,
+            Change::Note("a".to_string()),
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+        );
+        assert!(closed);
+        assert!(batch.rescan);
+    }
+
+    #[test]
+    fn test_collect_batch_stops_at_max_delay() {
+        let (queue, changes) = mpsc::channel();
+        let sender = thread::spawn(move || {
+            // Keep changes coming faster than the debounce window
+            for i in 0..50 {
+                if queue.send(Change::Note(i.to_string())).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+        });
+
+        let started = Instant::now();
+        let (batch, _) = collect_batch(
+            &changes,
+            Change::Rescan,
+            Duration::from_millis(100),
+            Duration::from_millis(50),
+        );
+        assert!(started.elapsed() < Duration::from_millis(200));
+        assert!(batch.paths.len() < 50);
+
+        drop(changes);
+        sender.join().unwrap();
+    }
+}
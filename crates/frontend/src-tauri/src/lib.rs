@@ -1,14 +1,22 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use tauri::{Emitter, Manager, State};
 use tauri_plugin_store::StoreExt;
 use zinnia_core::{
-    Note, NoteMetadata, NotesApi, RankingMode, WatcherEvent, cleanup_br_tags, setup_watcher,
+    ArchiveRule, DiffHunk, DiffKind, Error, FuzzyMatch, FuzzyMatcher, LinkKind, MatchSource,
+    MergeOutcome, MergeRegion, MergeStrategy, Note, NoteLink, NoteMetadata, NotesApi, Properties,
+    PropertyValue, RankingMode, Revision, RuleMatch, SearchHit, SearchMode, SyncProgress, TagCount,
+    UnarchiveCollision, UnarchiveOptions, Unarchived, UnifiedSearchResult, WatcherEvent,
+    cleanup_br_tags, setup_watcher,
 };
 
 // Application state holding the NotesApi instance
 pub struct AppState {
     notes_api: Arc<Mutex<NotesApi>>,
+    /// Set once the startup sync has finished
+    synced: Arc<AtomicBool>,
 }
 
 // Serializable versions of the core types for Tauri/JSON
@@ -18,6 +26,8 @@ pub struct NoteDTO {
     path: String,
     content: String,
     modified: u64, // Unix timestamp
+    properties: Option<BTreeMap<String, PropertyValueDTO>>,
+    content_hash: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -26,6 +36,176 @@ pub struct NoteMetadataDTO {
     path: String,
     modified: u64, // Unix timestamp
     archived: bool,
+    properties: Option<BTreeMap<String, PropertyValueDTO>>,
+}
+
+// Front matter values serialize as a plain string or an array of strings
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PropertyValueDTO {
+    Text(String),
+    List(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NoteLinkDTO {
+    source_path: String,
+    target_path: String,
+    kind: String, // "wiki" or "markdown"
+    resolved: bool,
+}
+
+// Byte range within a string
+#[derive(Serialize, Deserialize)]
+pub struct MatchRangeDTO {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SearchHitDTO {
+    note: NoteMetadataDTO,
+    snippet: String,
+    snippet_matches: Vec<MatchRangeDTO>,
+    matches: Vec<MatchRangeDTO>,
+    bm25: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FuzzyMatchDTO {
+    note: NoteMetadataDTO,
+    score: i32,
+    match_indices: Vec<usize>, // Character indices in note.path
+    in_note_name: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UnifiedSearchResultDTO {
+    note: NoteMetadataDTO,
+    source: String, // "path", "content" or "both"
+    score: f64,
+}
+
+// Outcome of save_note; a conflict is a result rather than an error so the UI can merge
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum SaveResultDTO {
+    Saved {
+        content_hash: Option<String>, // None when saved without an expected hash
+    },
+    Conflict {
+        disk_content: String,
+        disk_hash: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategyDTO {
+    Structured,
+    ConflictMarkers,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnarchiveCollisionDTO {
+    Rename,
+    Fail,
+    Merge,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UnarchivedDTO {
+    path: String,
+    rewritten: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MergeRegionDTO {
+    Resolved {
+        lines: Vec<String>,
+    },
+    Conflict {
+        base: Vec<String>,
+        ours: Vec<String>,
+        theirs: Vec<String>,
+    },
+}
+
+// Outcome of merge_note; "clean" and "marked" were saved, "conflicts" was not
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum MergeOutcomeDTO {
+    Clean { content: String },
+    Conflicts { regions: Vec<MergeRegionDTO> },
+    Marked { content: String },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RevisionDTO {
+    id: i64,
+    created_at: u64, // Unix timestamp
+    size: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchiveRuleDTO {
+    id: i64,
+    parent: Option<String>,
+    inactive_secs: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RuleMatchDTO {
+    rule_id: i64,
+    path: String,
+    last_active: u64, // Unix timestamp
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SyncProgressDTO {
+    done: usize,
+    total: usize,
+}
+
+// What the filesystem watcher saw happen to a note, tagged by kind
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WatcherEventDTO {
+    Created {
+        path: String,
+    },
+    Modified {
+        path: String,
+        old_hash: String,
+        new_hash: String,
+    },
+    Deleted {
+        path: String,
+    },
+    Moved {
+        from: String,
+        to: String,
+    },
+    Archived {
+        path: String,
+    },
+    FrecencyUpdated,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DiffHunkDTO {
+    kind: String, // "added", "removed" or "unchanged"
+    old_start: usize,
+    new_start: usize,
+    lines: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TagCountDTO {
+    tag: String,
+    count: i64,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -35,6 +215,22 @@ pub enum RankingModeDTO {
     Frecency,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FuzzyMatcherDTO {
+    Subsequence,
+    TypoTolerant,
+}
+
+impl From<FuzzyMatcherDTO> for FuzzyMatcher {
+    fn from(dto: FuzzyMatcherDTO) -> Self {
+        match dto {
+            FuzzyMatcherDTO::Subsequence => FuzzyMatcher::Subsequence,
+            FuzzyMatcherDTO::TypoTolerant => FuzzyMatcher::TypoTolerant,
+        }
+    }
+}
+
 impl From<RankingModeDTO> for RankingMode {
     fn from(dto: RankingModeDTO) -> Self {
         match dto {
@@ -44,6 +240,31 @@ impl From<RankingModeDTO> for RankingMode {
     }
 }
 
+impl From<PropertyValue> for PropertyValueDTO {
+    fn from(value: PropertyValue) -> Self {
+        match value {
+            PropertyValue::Text(text) => PropertyValueDTO::Text(text),
+            PropertyValue::List(items) => PropertyValueDTO::List(items),
+        }
+    }
+}
+
+impl From<PropertyValueDTO> for PropertyValue {
+    fn from(dto: PropertyValueDTO) -> Self {
+        match dto {
+            PropertyValueDTO::Text(text) => PropertyValue::Text(text),
+            PropertyValueDTO::List(items) => PropertyValue::List(items),
+        }
+    }
+}
+
+fn properties_to_dto(properties: Properties) -> BTreeMap<String, PropertyValueDTO> {
+    properties
+        .into_iter()
+        .map(|(key, value)| (key, value.into()))
+        .collect()
+}
+
 // Convert core types to DTOs
 impl From<Note> for NoteDTO {
     fn from(note: Note) -> Self {
@@ -56,6 +277,8 @@ impl From<Note> for NoteDTO {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            properties: note.properties.map(properties_to_dto),
+            content_hash: note.content_hash,
         }
     }
 }
@@ -71,12 +294,220 @@ impl From<NoteMetadata> for NoteMetadataDTO {
                 .unwrap()
                 .as_secs(),
             archived: meta.archived,
+            properties: meta.properties.map(properties_to_dto),
+        }
+    }
+}
+
+impl From<NoteLink> for NoteLinkDTO {
+    fn from(link: NoteLink) -> Self {
+        NoteLinkDTO {
+            source_path: link.source_path,
+            target_path: link.target_path,
+            kind: match link.kind {
+                LinkKind::Wiki => "wiki".to_string(),
+                LinkKind::Markdown => "markdown".to_string(),
+            },
+            resolved: link.resolved,
+        }
+    }
+}
+
+impl From<SearchHit> for SearchHitDTO {
+    fn from(hit: SearchHit) -> Self {
+        let ranges = |ranges: Vec<std::ops::Range<usize>>| {
+            ranges
+                .into_iter()
+                .map(|r| MatchRangeDTO {
+                    start: r.start,
+                    end: r.end,
+                })
+                .collect()
+        };
+        SearchHitDTO {
+            note: hit.note.into(),
+            snippet: hit.snippet,
+            snippet_matches: ranges(hit.snippet_matches),
+            matches: ranges(hit.matches),
+            bm25: hit.bm25,
+        }
+    }
+}
+
+impl From<FuzzyMatch> for FuzzyMatchDTO {
+    fn from(m: FuzzyMatch) -> Self {
+        FuzzyMatchDTO {
+            note: m.note.into(),
+            score: m.score,
+            match_indices: m.match_indices,
+            in_note_name: m.in_note_name,
+        }
+    }
+}
+
+impl From<UnifiedSearchResult> for UnifiedSearchResultDTO {
+    fn from(result: UnifiedSearchResult) -> Self {
+        UnifiedSearchResultDTO {
+            note: result.note.into(),
+            source: match result.source {
+                MatchSource::Path => "path".to_string(),
+                MatchSource::Content => "content".to_string(),
+                MatchSource::Both => "both".to_string(),
+            },
+            score: result.score,
+        }
+    }
+}
+
+impl From<MergeStrategyDTO> for MergeStrategy {
+    fn from(strategy: MergeStrategyDTO) -> Self {
+        match strategy {
+            MergeStrategyDTO::Structured => MergeStrategy::Structured,
+            MergeStrategyDTO::ConflictMarkers => MergeStrategy::ConflictMarkers,
+        }
+    }
+}
+
+impl From<UnarchiveCollisionDTO> for UnarchiveCollision {
+    fn from(collision: UnarchiveCollisionDTO) -> Self {
+        match collision {
+            UnarchiveCollisionDTO::Rename => UnarchiveCollision::Rename,
+            UnarchiveCollisionDTO::Fail => UnarchiveCollision::Fail,
+            UnarchiveCollisionDTO::Merge => UnarchiveCollision::Merge,
+        }
+    }
+}
+
+impl From<Unarchived> for UnarchivedDTO {
+    fn from(unarchived: Unarchived) -> Self {
+        UnarchivedDTO {
+            path: unarchived.path,
+            rewritten: unarchived.rewritten,
+        }
+    }
+}
+
+impl From<MergeOutcome> for MergeOutcomeDTO {
+    fn from(outcome: MergeOutcome) -> Self {
+        match outcome {
+            MergeOutcome::Clean(content) => MergeOutcomeDTO::Clean { content },
+            MergeOutcome::Conflicts(regions) => MergeOutcomeDTO::Conflicts {
+                regions: regions
+                    .into_iter()
+                    .map(|region| match region {
+                        MergeRegion::Resolved(lines) => MergeRegionDTO::Resolved { lines },
+                        MergeRegion::Conflict { base, ours, theirs } => {
+                            MergeRegionDTO::Conflict { base, ours, theirs }
+                        }
+                    })
+                    .collect(),
+            },
+            MergeOutcome::Marked(content) => MergeOutcomeDTO::Marked { content },
+        }
+    }
+}
+
+impl From<Revision> for RevisionDTO {
+    fn from(revision: Revision) -> Self {
+        RevisionDTO {
+            id: revision.id,
+            created_at: revision
+                .created_at
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            size: revision.size,
+        }
+    }
+}
+
+impl From<ArchiveRule> for ArchiveRuleDTO {
+    fn from(rule: ArchiveRule) -> Self {
+        ArchiveRuleDTO {
+            id: rule.id,
+            parent: rule.parent,
+            inactive_secs: rule.inactive_for.as_secs(),
+        }
+    }
+}
+
+impl From<RuleMatch> for RuleMatchDTO {
+    fn from(rule_match: RuleMatch) -> Self {
+        RuleMatchDTO {
+            rule_id: rule_match.rule_id,
+            path: rule_match.path,
+            last_active: rule_match
+                .last_active
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        }
+    }
+}
+
+impl From<SyncProgress> for SyncProgressDTO {
+    fn from(progress: SyncProgress) -> Self {
+        SyncProgressDTO {
+            done: progress.done,
+            total: progress.total,
+        }
+    }
+}
+
+impl From<WatcherEvent> for WatcherEventDTO {
+    fn from(event: WatcherEvent) -> Self {
+        match event {
+            WatcherEvent::Created { path } => WatcherEventDTO::Created { path },
+            WatcherEvent::Modified {
+                path,
+                old_hash,
+                new_hash,
+            } => WatcherEventDTO::Modified {
+                path,
+                old_hash,
+                new_hash,
+            },
+            WatcherEvent::Deleted { path } => WatcherEventDTO::Deleted { path },
+            WatcherEvent::Moved { from, to } => WatcherEventDTO::Moved { from, to },
+            WatcherEvent::Archived { path } => WatcherEventDTO::Archived { path },
+            WatcherEvent::FrecencyUpdated => WatcherEventDTO::FrecencyUpdated,
+        }
+    }
+}
+
+impl From<DiffHunk> for DiffHunkDTO {
+    fn from(hunk: DiffHunk) -> Self {
+        DiffHunkDTO {
+            kind: match hunk.kind {
+                DiffKind::Added => "added".to_string(),
+                DiffKind::Removed => "removed".to_string(),
+                DiffKind::Unchanged => "unchanged".to_string(),
+            },
+            old_start: hunk.old_start,
+            new_start: hunk.new_start,
+            lines: hunk.lines,
+        }
+    }
+}
+
+impl From<TagCount> for TagCountDTO {
+    fn from(tag: TagCount) -> Self {
+        TagCountDTO {
+            tag: tag.tag,
+            count: tag.count,
         }
     }
 }
 
 // Tauri Commands
 
+/// Whether the startup sync has finished. Until then, commands that use the
+/// notes API wait for it.
+#[tauri::command]
+fn is_synced(state: State<AppState>) -> bool {
+    state.synced.load(Ordering::SeqCst)
+}
+
 #[tauri::command]
 fn create_note(path: String, state: State<AppState>) -> Result<NoteDTO, String> {
     let mut api = state.notes_api.lock().unwrap();
@@ -94,9 +525,45 @@ fn get_note(path: String, state: State<AppState>) -> Result<NoteDTO, String> {
 }
 
 #[tauri::command]
-fn save_note(path: String, content: String, state: State<AppState>) -> Result<(), String> {
+fn save_note(
+    path: String,
+    content: String,
+    expected_hash: Option<String>,
+    state: State<AppState>,
+) -> Result<SaveResultDTO, String> {
+    let mut api = state.notes_api.lock().unwrap();
+    let Some(expected_hash) = expected_hash else {
+        return api
+            .save_note(&path, &content)
+            .map(|_| SaveResultDTO::Saved { content_hash: None })
+            .map_err(|e| format!("{:?}", e));
+    };
+
+    match api.save_note_if_unchanged(&path, &content, &expected_hash) {
+        Ok(hash) => Ok(SaveResultDTO::Saved {
+            content_hash: Some(hash),
+        }),
+        Err(Error::Conflict {
+            disk_content,
+            disk_hash,
+        }) => Ok(SaveResultDTO::Conflict {
+            disk_content,
+            disk_hash,
+        }),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+#[tauri::command]
+fn merge_note(
+    path: String,
+    content: String,
+    strategy: MergeStrategyDTO,
+    state: State<AppState>,
+) -> Result<MergeOutcomeDTO, String> {
     let mut api = state.notes_api.lock().unwrap();
-    api.save_note(&path, &content)
+    api.merge_note(&path, &content, strategy.into())
+        .map(|outcome| outcome.into())
         .map_err(|e| format!("{:?}", e))
 }
 
@@ -107,7 +574,11 @@ fn delete_note(path: String, state: State<AppState>) -> Result<(), String> {
 }
 
 #[tauri::command]
-fn rename_note(old_path: String, new_path: String, state: State<AppState>) -> Result<(), String> {
+fn rename_note(
+    old_path: String,
+    new_path: String,
+    state: State<AppState>,
+) -> Result<Vec<String>, String> {
     let mut api = state.notes_api.lock().unwrap();
     api.rename_note(&old_path, &new_path)
         .map_err(|e| format!("{:?}", e))
@@ -157,40 +628,282 @@ fn fuzzy_search_notes(
     limit: Option<usize>,
     ranking_mode: RankingModeDTO,
     context_path: Option<String>,
+    matcher: Option<FuzzyMatcherDTO>,
+    state: State<AppState>,
+) -> Result<Vec<FuzzyMatchDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    let matcher = matcher.map_or(FuzzyMatcher::Subsequence, |m| m.into());
+    api.fuzzy_search_with(
+        &query,
+        limit,
+        ranking_mode.into(),
+        context_path.as_deref(),
+        matcher,
+    )
+    .map(|results| results.into_iter().map(|r| r.into()).collect())
+    .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn unified_search(
+    query: String,
+    limit: Option<usize>,
+    ranking_mode: RankingModeDTO,
+    state: State<AppState>,
+) -> Result<Vec<UnifiedSearchResultDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.unified_search(&query, limit, ranking_mode.into())
+        .map(|results| results.into_iter().map(|r| r.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn search_notes(
+    query: String,
+    advanced: Option<bool>,
     state: State<AppState>,
 ) -> Result<Vec<NoteMetadataDTO>, String> {
     let api = state.notes_api.lock().unwrap();
-    api.fuzzy_search(&query, limit, ranking_mode.into(), context_path.as_deref())
+    let mode = if advanced.unwrap_or(false) {
+        SearchMode::Advanced
+    } else {
+        SearchMode::Text
+    };
+    api.search_with_mode(&query, mode)
         .map(|results| results.into_iter().map(|r| r.into()).collect())
         .map_err(|e| format!("{:?}", e))
 }
 
 #[tauri::command]
-fn search_notes(query: String, state: State<AppState>) -> Result<Vec<NoteMetadataDTO>, String> {
+fn search_with_snippets(
+    query: String,
+    limit: usize,
+    offset: usize,
+    state: State<AppState>,
+) -> Result<Vec<SearchHitDTO>, String> {
     let api = state.notes_api.lock().unwrap();
-    api.search(&query)
+    api.search_with_snippets(&query, limit, offset)
+        .map(|hits| hits.into_iter().map(|h| h.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn query_notes(query: String, state: State<AppState>) -> Result<Vec<NoteMetadataDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.query_notes(&query)
         .map(|results| results.into_iter().map(|r| r.into()).collect())
         .map_err(|e| format!("{:?}", e))
 }
 
 #[tauri::command]
-fn archive_note(path: String, state: State<AppState>) -> Result<(), String> {
+fn get_backlinks(path: String, state: State<AppState>) -> Result<Vec<NoteMetadataDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.get_backlinks(&path)
+        .map(|notes| notes.into_iter().map(|n| n.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn get_outgoing_links(path: String, state: State<AppState>) -> Result<Vec<NoteLinkDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.get_outgoing_links(&path)
+        .map(|links| links.into_iter().map(|l| l.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn get_properties(
+    path: String,
+    state: State<AppState>,
+) -> Result<BTreeMap<String, PropertyValueDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.get_properties(&path)
+        .map(properties_to_dto)
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn set_property(
+    path: String,
+    key: String,
+    value: PropertyValueDTO,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let mut api = state.notes_api.lock().unwrap();
+    api.set_property(&path, &key, PropertyValue::from(value))
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn list_tags(state: State<AppState>) -> Result<Vec<TagCountDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.list_tags()
+        .map(|tags| tags.into_iter().map(|t| t.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn notes_with_tag(tag: String, state: State<AppState>) -> Result<Vec<NoteMetadataDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.notes_with_tag(&tag)
+        .map(|notes| notes.into_iter().map(|n| n.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn rename_tag(
+    old_tag: String,
+    new_tag: String,
+    state: State<AppState>,
+) -> Result<Vec<String>, String> {
+    let mut api = state.notes_api.lock().unwrap();
+    api.rename_tag(&old_tag, &new_tag)
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn list_revisions(path: String, state: State<AppState>) -> Result<Vec<RevisionDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.list_revisions(&path)
+        .map(|revisions| revisions.into_iter().map(|r| r.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn get_revision(path: String, revision_id: i64, state: State<AppState>) -> Result<String, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.get_revision(&path, revision_id)
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn restore_revision(path: String, revision_id: i64, state: State<AppState>) -> Result<(), String> {
+    let mut api = state.notes_api.lock().unwrap();
+    api.restore_revision(&path, revision_id)
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn diff_revisions(
+    path: String,
+    a: i64,
+    b: i64,
+    state: State<AppState>,
+) -> Result<Vec<DiffHunkDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.diff_revisions(&path, a, b)
+        .map(|hunks| hunks.into_iter().map(|h| h.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn diff_with_disk(path: String, state: State<AppState>) -> Result<Vec<DiffHunkDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.diff_with_disk(&path)
+        .map(|hunks| hunks.into_iter().map(|h| h.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn archive_note(path: String, state: State<AppState>) -> Result<Vec<String>, String> {
     let mut api = state.notes_api.lock().unwrap();
     api.archive_note(&path).map_err(|e| format!("{:?}", e))
 }
 
 #[tauri::command]
-fn unarchive_note(path: String, state: State<AppState>) -> Result<(), String> {
+fn unarchive_note(path: String, state: State<AppState>) -> Result<Vec<String>, String> {
     let mut api = state.notes_api.lock().unwrap();
     api.unarchive_note(&path).map_err(|e| format!("{:?}", e))
 }
 
+#[tauri::command]
+fn unarchive_note_with_options(
+    path: String,
+    on_collision: UnarchiveCollisionDTO,
+    state: State<AppState>,
+) -> Result<UnarchivedDTO, String> {
+    let mut api = state.notes_api.lock().unwrap();
+    let options = UnarchiveOptions {
+        on_collision: on_collision.into(),
+    };
+    api.unarchive_note_with_options(&path, options)
+        .map(|unarchived| unarchived.into())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn list_archived(
+    parent: Option<String>,
+    state: State<AppState>,
+) -> Result<Vec<NoteMetadataDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.list_archived(parent.as_deref())
+        .map(|notes| notes.into_iter().map(|n| n.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn add_archive_rule(
+    parent: Option<String>,
+    inactive_secs: u64,
+    state: State<AppState>,
+) -> Result<ArchiveRuleDTO, String> {
+    let mut api = state.notes_api.lock().unwrap();
+    api.add_archive_rule(
+        parent.as_deref(),
+        std::time::Duration::from_secs(inactive_secs),
+    )
+    .map(|rule| rule.into())
+    .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn list_archive_rules(state: State<AppState>) -> Result<Vec<ArchiveRuleDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.list_archive_rules()
+        .map(|rules| rules.into_iter().map(|r| r.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn remove_archive_rule(id: i64, state: State<AppState>) -> Result<(), String> {
+    let mut api = state.notes_api.lock().unwrap();
+    api.remove_archive_rule(id).map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn preview_rules(state: State<AppState>) -> Result<Vec<RuleMatchDTO>, String> {
+    let api = state.notes_api.lock().unwrap();
+    api.preview_rules()
+        .map(|matches| matches.into_iter().map(|m| m.into()).collect())
+        .map_err(|e| format!("{:?}", e))
+}
+
+/// Runs the archive rules. Called by the frontend once on startup.
+#[tauri::command]
+fn apply_rules(state: State<AppState>) -> Result<Vec<String>, String> {
+    let mut api = state.notes_api.lock().unwrap();
+    api.apply_rules().map_err(|e| format!("{:?}", e))
+}
+
 #[tauri::command]
 fn trash_note(path: String, state: State<AppState>) -> Result<(), String> {
     let mut api = state.notes_api.lock().unwrap();
     api.trash_note(&path).map_err(|e| format!("{:?}", e))
 }
 
+#[tauri::command]
+fn undo(state: State<AppState>) -> Result<bool, String> {
+    let mut api = state.notes_api.lock().unwrap();
+    api.undo().map_err(|e| format!("{:?}", e))
+}
+
+#[tauri::command]
+fn redo(state: State<AppState>) -> Result<bool, String> {
+    let mut api = state.notes_api.lock().unwrap();
+    api.redo().map_err(|e| format!("{:?}", e))
+}
+
 #[tauri::command]
 async fn download_image(
     note_path: String,
@@ -349,15 +1062,15 @@ fn run_br_tags_migration(app: &tauri::App, notes_api: &Arc<Mutex<NotesApi>>) {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let mut api =
+    let api =
         NotesApi::with_default_path(cfg!(debug_assertions)).expect("Failed to initialize NotesApi");
 
-    api.startup_sync().expect("Failed to sync notes database");
-
     let notes_api = Arc::new(Mutex::new(api));
+    let synced = Arc::new(AtomicBool::new(false));
 
     let state = AppState {
         notes_api: Arc::clone(&notes_api),
+        synced: Arc::clone(&synced),
     };
 
     tauri::Builder::default()
@@ -369,9 +1082,11 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .manage(state)
         .invoke_handler(tauri::generate_handler![
+            is_synced,
             create_note,
             get_note,
             save_note,
+            merge_note,
             delete_note,
             rename_note,
             get_children,
@@ -380,10 +1095,34 @@ pub fn run() {
             get_root_notes,
             get_all_notes,
             fuzzy_search_notes,
+            unified_search,
             search_notes,
+            search_with_snippets,
+            query_notes,
+            get_backlinks,
+            get_outgoing_links,
+            get_properties,
+            set_property,
+            list_tags,
+            notes_with_tag,
+            rename_tag,
+            list_revisions,
+            get_revision,
+            restore_revision,
+            diff_revisions,
+            diff_with_disk,
             archive_note,
             unarchive_note,
+            unarchive_note_with_options,
+            list_archived,
+            add_archive_rule,
+            list_archive_rules,
+            remove_archive_rule,
+            preview_rules,
+            apply_rules,
             trash_note,
+            undo,
+            redo,
             download_image,
             resolve_image_path,
             get_note_file_path,
@@ -405,25 +1144,46 @@ pub fn run() {
                 });
             }
 
-            // Setup filesystem watcher with event emission
-            let _watcher = setup_watcher(
-                notes_api,
-                Some(move |event| {
-                    let event_name = match event {
-                        WatcherEvent::NotesChanged => "notes:changed",
-                        WatcherEvent::NotesRenamed => "notes:renamed",
-                        WatcherEvent::FrecencyUpdated => "notes:frecency",
-                    };
-
-                    // Emit event to frontend
-                    if let Err(e) = app_handle.emit(event_name, ()) {
-                        eprintln!("Failed to emit watcher event: {:?}", e);
+            // Sync in the background so the window can show progress instead of
+            // blocking startup, then start watching for changes
+            let app_handle_sync = app.handle().clone();
+            std::thread::spawn(move || {
+                {
+                    let mut api = notes_api.lock().unwrap();
+                    let result = api.startup_sync_with_progress(|progress| {
+                        let progress: SyncProgressDTO = progress.into();
+                        if let Err(e) = app_handle_sync.emit("notes:sync-progress", progress) {
+                            eprintln!("Failed to emit sync progress: {:?}", e);
+                        }
+                    });
+                    if let Err(e) = result {
+                        eprintln!("Failed to sync notes database: {:?}", e);
                     }
-                }),
-            );
+                }
+
+                synced.store(true, Ordering::SeqCst);
+                if let Err(e) = app_handle_sync.emit("notes:synced", ()) {
+                    eprintln!("Failed to emit synced event: {:?}", e);
+                }
+
+                // Setup filesystem watcher with event emission
+                let watcher = setup_watcher(
+                    notes_api,
+                    Some(move |events: Vec<WatcherEvent>| {
+                        // Emit each batch to the frontend as one event, so it can
+                        // invalidate exactly the notes that changed
+                        let events: Vec<WatcherEventDTO> =
+                            events.into_iter().map(Into::into).collect();
+                        if let Err(e) = app_handle.emit("notes:changed", events) {
+                            eprintln!("Failed to emit watcher event: {:?}", e);
+                        }
+                    }),
+                );
+
+                // Keep watcher alive for app lifetime
+                app_handle_sync.manage(watcher);
+            });
 
-            // Keep watcher alive for app lifetime
-            app.manage(_watcher);
             Ok(())
         })
         .run(tauri::generate_context!())
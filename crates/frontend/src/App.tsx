@@ -1,5 +1,6 @@
-import { onMount, createSignal, onCleanup } from "solid-js";
-import { NotesProvider, useNotes } from "./api";
+import { onMount, createSignal, onCleanup, Show } from "solid-js";
+import { listen } from "@tauri-apps/api/event";
+import { NotesProvider, commands, useNotes } from "./api";
 import { Navigation } from "./components/Navigation";
 import EditorManager from "./components/editor/EditorManager";
 import { ToastProvider, useToast } from "./components/ui/Toast";
@@ -10,7 +11,7 @@ import { downloadAndInstallUpdate, restartApp } from "./utils/updater";
 import { getVersion } from "@tauri-apps/api/app";
 import { loadSettings } from "./utils/settings";
 import { getAppState, setAppState } from "./utils/appState";
-import type { NoteMetadata } from "./types";
+import type { NoteMetadata, SyncProgress } from "./types";
 
 function AppContent() {
   const isDev = import.meta.env.DEV;
@@ -31,6 +32,19 @@ function AppContent() {
       e.preventDefault();
       setShowSettings(true);
     }
+    // Cmd+Z / Cmd+Shift+Z undo and redo note operations, unless a text field
+    // or the editor has focus and handles them itself
+    const target = e.target as HTMLElement | null;
+    const isEditing = target?.closest(
+      "input, textarea, [contenteditable='true']",
+    );
+    if ((e.metaKey || e.ctrlKey) && e.key.toLowerCase() === "z" && !isEditing) {
+      e.preventDefault();
+      const redo = e.shiftKey;
+      (redo ? notes.redo() : notes.undo()).catch((err) =>
+        toast.error(`Failed to ${redo ? "redo" : "undo"}: ${err}`),
+      );
+    }
   };
 
   onMount(() => {
@@ -148,13 +162,56 @@ function AppContent() {
   );
 }
 
+function SyncScreen(props: { progress?: SyncProgress }) {
+  return (
+    <div class="flex h-screen flex-col items-center justify-center gap-2 text-sm opacity-60">
+      <div>Loading notes...</div>
+      {props.progress && props.progress.total > 0 && (
+        <div>
+          {props.progress.done} / {props.progress.total}
+        </div>
+      )}
+    </div>
+  );
+}
+
 function App() {
+  const [synced, setSynced] = createSignal(false);
+  const [progress, setProgress] = createSignal<SyncProgress>();
+
+  // The backend syncs the notes database in the background on startup; wait for
+  // it before loading notes, showing progress meanwhile
+  onMount(() => {
+    let unlistenProgress: (() => void) | undefined;
+    let unlistenSynced: (() => void) | undefined;
+
+    onCleanup(() => {
+      unlistenProgress?.();
+      unlistenSynced?.();
+    });
+
+    (async () => {
+      unlistenProgress = await listen<SyncProgress>(
+        "notes:sync-progress",
+        (event) => setProgress(event.payload),
+      );
+      unlistenSynced = await listen("notes:synced", () => setSynced(true));
+
+      // The sync may have finished before the listeners were registered
+      if (await commands.isSynced()) {
+        setSynced(true);
+      }
+    })();
+  });
+
   return (
-    <NotesProvider>
-      <ToastProvider>
-        <AppContent />
-      </ToastProvider>
-    </NotesProvider>
+    <Show when={synced()} fallback={<SyncScreen progress={progress()} />}>
+      <NotesProvider>
+        <ToastProvider>
+          <AppContent />
+        </ToastProvider>
+      </NotesProvider>
+    </Show>
   );
 }
 
@@ -13,7 +13,7 @@ import {
 } from "solid-js";
 import { listen } from "@tauri-apps/api/event";
 import { commands } from "./commands";
-import type { Note, NoteMetadata } from "../types";
+import type { Note, NoteMetadata, WatcherEvent } from "../types";
 import { setAppState } from "../utils/appState";
 
 interface NotesContextValue {
@@ -45,6 +45,8 @@ interface NotesContextValue {
   renameNote: (oldPath: string, newPath: string) => Promise<void>;
   archiveNote: (path: string) => Promise<void>;
   unarchiveNote: (path: string) => Promise<void>;
+  undo: () => Promise<void>;
+  redo: () => Promise<void>;
 
   // Refresh helpers
   refetchCurrent: () => void;
@@ -188,42 +190,97 @@ export function NotesProvider(props: ParentProps) {
     refetchCurrent();
   };
 
+  // Undo and redo can touch any part of the tree, so refresh all of it
+  const refetchAll = () => {
+    refetchChildren();
+    refetchRootNotes();
+    refetchAncestors();
+    refetchCurrent();
+  };
+
+  const undo = async () => {
+    if (await commands.undo()) {
+      refetchAll();
+    }
+  };
+
+  const redo = async () => {
+    if (await commands.redo()) {
+      refetchAll();
+    }
+  };
+
   // Listen for filesystem watcher events from Tauri backend
   onMount(() => {
+    // Archive notes that have gone stale according to the archive rules
+    commands
+      .applyRules()
+      .then((archived) => {
+        if (archived.length > 0) {
+          refetchAll();
+        }
+      })
+      .catch((err) => console.error("Failed to apply archive rules:", err));
+
     let unlistenChanged: (() => void) | undefined;
-    let unlistenRenamed: (() => void) | undefined;
     let unlistenFrecency: (() => void) | undefined;
 
+    // Force reload by toggling the path
+    const reload = (path: string) => {
+      setCurrentPath("");
+      // Use setTimeout to ensure the effect runs twice
+      setTimeout(() => setCurrentPath(path), 0);
+    };
+
     // Setup async listeners
     (async () => {
-      // Listen for note changes (create, update, delete)
-      // The backend now uses content hash comparison, so this event only fires
-      // when content actually changes (not on our own saves with identical content)
-      unlistenChanged = await listen("notes:changed", () => {
-        console.log(
-          "File watcher detected external changes, reloading current note...",
-        );
-        // Force reload by toggling the path
-        const path = currentPath();
-        if (path) {
-          setCurrentPath("");
-          // Use setTimeout to ensure the effect runs twice
-          setTimeout(() => setCurrentPath(path), 0);
-        }
-      });
-
-      // Listen for note renames/moves
-      unlistenRenamed = await listen("notes:renamed", () => {
-        console.log(
-          "File watcher detected external rename/move, reloading current note...",
-        );
-        // Force reload by toggling the path
-        const path = currentPath();
-        if (path) {
-          setCurrentPath("");
-          setTimeout(() => setCurrentPath(path), 0);
-        }
-      });
+      // Listen for external note changes, batched per burst of changes.
+      // The backend uses content hash comparison, so our own saves with
+      // identical content don't show up here
+      unlistenChanged = await listen<WatcherEvent[]>(
+        "notes:changed",
+        (event) => {
+          const path = currentPath();
+          let treeChanged = false;
+
+          for (const change of event.payload) {
+            switch (change.kind) {
+              case "modified":
+                if (path && change.path === path) {
+                  console.log(
+                    "File watcher detected external changes, reloading current note...",
+                  );
+                  reload(path);
+                }
+                break;
+              case "moved":
+                // Follow the current note to its new location
+                if (path && change.from === path) {
+                  setCurrentPath(change.to);
+                }
+                treeChanged = true;
+                break;
+              case "deleted":
+              case "archived":
+                if (path && change.path === path) {
+                  setCurrentPath("");
+                }
+                treeChanged = true;
+                break;
+              case "created":
+              case "frecency_updated":
+                treeChanged = true;
+                break;
+            }
+          }
+
+          if (treeChanged) {
+            refetchChildren();
+            refetchRootNotes();
+            refetchAncestors();
+          }
+        },
+      );
 
       // Listen for frecency updates
       unlistenFrecency = await listen("notes:frecency", () => {
@@ -237,7 +294,6 @@ export function NotesProvider(props: ParentProps) {
     // Register cleanup synchronously before async work completes
     onCleanup(() => {
       unlistenChanged?.();
-      unlistenRenamed?.();
       unlistenFrecency?.();
     });
   });
@@ -262,6 +318,8 @@ export function NotesProvider(props: ParentProps) {
     renameNote,
     archiveNote,
     unarchiveNote,
+    undo,
+    redo,
     refetchCurrent,
     refetchChildren,
     refetchAncestors,
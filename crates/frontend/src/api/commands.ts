@@ -1,21 +1,57 @@
 // Tauri command bindings for notes API
 import { invoke } from "@tauri-apps/api/core";
-import type { Note, NoteMetadata } from "../types";
+import type {
+  ArchiveRule,
+  DiffHunk,
+  FuzzyMatch,
+  MergeOutcome,
+  Note,
+  NoteLink,
+  NoteMetadata,
+  Properties,
+  PropertyValue,
+  Revision,
+  RuleMatch,
+  SaveResult,
+  SearchHit,
+  TagCount,
+  Unarchived,
+  UnifiedSearchResult,
+} from "../types";
 
 export type RankingMode = "visits" | "frecency";
 
+export type FuzzyMatcher = "subsequence" | "typo_tolerant";
+
+export type MergeStrategy = "structured" | "conflict_markers";
+
+/** What to do when a note exists at an archived note's original path */
+export type UnarchiveCollision = "rename" | "fail" | "merge";
+
 export const commands = {
+  /** Whether the startup sync has finished */
+  isSynced: () => invoke<boolean>("is_synced"),
+
   createNote: (path: string) => invoke<Note>("create_note", { path }),
 
   getNote: (path: string) => invoke<Note>("get_note", { path }),
 
-  saveNote: (path: string, content: string) =>
-    invoke<void>("save_note", { path, content }),
+  /** With expectedHash, refuses to overwrite changes made on disk since the note was read */
+  saveNote: (path: string, content: string, expectedHash?: string) =>
+    invoke<SaveResult>("save_note", {
+      path,
+      content,
+      expectedHash: expectedHash ?? null,
+    }),
+
+  /** Three-way merges the edited content with the note on disk */
+  mergeNote: (path: string, content: string, strategy: MergeStrategy) =>
+    invoke<MergeOutcome>("merge_note", { path, content, strategy }),
 
   deleteNote: (path: string) => invoke<void>("delete_note", { path }),
 
   renameNote: (oldPath: string, newPath: string) =>
-    invoke<void>("rename_note", { oldPath, newPath }),
+    invoke<string[]>("rename_note", { oldPath, newPath }),
 
   getChildren: (path: string) =>
     invoke<NoteMetadata[]>("get_children", { path }),
@@ -34,23 +70,103 @@ export const commands = {
     limit?: number,
     rankingMode?: RankingMode,
     contextPath?: string | null,
+    matcher?: FuzzyMatcher,
   ) =>
-    invoke<NoteMetadata[]>("fuzzy_search_notes", {
+    invoke<FuzzyMatch[]>("fuzzy_search_notes", {
       query,
       limit,
       rankingMode: rankingMode || "visits",
       contextPath: contextPath ?? null,
+      matcher: matcher || "subsequence",
+    }),
+
+  unifiedSearch: (query: string, limit?: number, rankingMode?: RankingMode) =>
+    invoke<UnifiedSearchResult[]>("unified_search", {
+      query,
+      limit,
+      rankingMode: rankingMode || "visits",
     }),
 
-  searchNotes: (query: string) =>
-    invoke<NoteMetadata[]>("search_notes", { query }),
+  searchNotes: (query: string, advanced = false) =>
+    invoke<NoteMetadata[]>("search_notes", { query, advanced }),
+
+  searchWithSnippets: (query: string, limit: number, offset: number) =>
+    invoke<SearchHit[]>("search_with_snippets", { query, limit, offset }),
+
+  queryNotes: (query: string) =>
+    invoke<NoteMetadata[]>("query_notes", { query }),
+
+  getBacklinks: (path: string) =>
+    invoke<NoteMetadata[]>("get_backlinks", { path }),
+
+  getOutgoingLinks: (path: string) =>
+    invoke<NoteLink[]>("get_outgoing_links", { path }),
+
+  getProperties: (path: string) =>
+    invoke<Properties>("get_properties", { path }),
+
+  setProperty: (path: string, key: string, value: PropertyValue) =>
+    invoke<void>("set_property", { path, key, value }),
+
+  listTags: () => invoke<TagCount[]>("list_tags"),
 
-  archiveNote: (path: string) => invoke<void>("archive_note", { path }),
+  notesWithTag: (tag: string) =>
+    invoke<NoteMetadata[]>("notes_with_tag", { tag }),
 
-  unarchiveNote: (path: string) => invoke<void>("unarchive_note", { path }),
+  renameTag: (oldTag: string, newTag: string) =>
+    invoke<string[]>("rename_tag", { oldTag, newTag }),
+
+  listRevisions: (path: string) =>
+    invoke<Revision[]>("list_revisions", { path }),
+
+  getRevision: (path: string, revisionId: number) =>
+    invoke<string>("get_revision", { path, revisionId }),
+
+  restoreRevision: (path: string, revisionId: number) =>
+    invoke<void>("restore_revision", { path, revisionId }),
+
+  diffRevisions: (path: string, a: number, b: number) =>
+    invoke<DiffHunk[]>("diff_revisions", { path, a, b }),
+
+  diffWithDisk: (path: string) =>
+    invoke<DiffHunk[]>("diff_with_disk", { path }),
+
+  archiveNote: (path: string) => invoke<string[]>("archive_note", { path }),
+
+  unarchiveNote: (path: string) =>
+    invoke<string[]>("unarchive_note", { path }),
+
+  unarchiveNoteWithOptions: (path: string, onCollision: UnarchiveCollision) =>
+    invoke<Unarchived>("unarchive_note_with_options", { path, onCollision }),
+
+  /** Notes archived directly under parent, or at the root if parent is omitted */
+  listArchived: (parent?: string) =>
+    invoke<NoteMetadata[]>("list_archived", { parent: parent ?? null }),
+
+  /** Archives children of parent (root notes if omitted) inactive for inactiveSecs */
+  addArchiveRule: (inactiveSecs: number, parent?: string) =>
+    invoke<ArchiveRule>("add_archive_rule", {
+      parent: parent ?? null,
+      inactiveSecs,
+    }),
+
+  listArchiveRules: () => invoke<ArchiveRule[]>("list_archive_rules"),
+
+  removeArchiveRule: (id: number) => invoke<void>("remove_archive_rule", { id }),
+
+  /** Notes the archive rules would archive now, without archiving them */
+  previewRules: () => invoke<RuleMatch[]>("preview_rules"),
+
+  /** Runs the archive rules, returning the paths of the archived notes */
+  applyRules: () => invoke<string[]>("apply_rules"),
 
   trashNote: (path: string) => invoke<void>("trash_note", { path }),
 
+  /** Undoes the last create, rename, archive, unarchive, trash or delete; false if none */
+  undo: () => invoke<boolean>("undo"),
+
+  redo: () => invoke<boolean>("redo"),
+
   downloadImage: (notePath: string, imageUrl: string) =>
     invoke<string>("download_image", { notePath, imageUrl }),
 
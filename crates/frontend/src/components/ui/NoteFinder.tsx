@@ -1,6 +1,6 @@
 import { createSignal, createEffect, For, onMount, onCleanup } from "solid-js";
 import { commands, type RankingMode } from "../../api/commands";
-import type { NoteMetadata } from "../../types";
+import type { FuzzyMatch, NoteMetadata } from "../../types";
 import { Modal } from "../primitives/Modal";
 
 export function NoteFinder(props: {
@@ -13,7 +13,7 @@ export function NoteFinder(props: {
   contextPath?: string | null;
 }) {
   const [query, setQuery] = createSignal("");
-  const [results, setResults] = createSignal<NoteMetadata[]>([]);
+  const [results, setResults] = createSignal<FuzzyMatch[]>([]);
   const [selectedIndex, setSelectedIndex] = createSignal(0);
   const [isLoading, setIsLoading] = createSignal(false);
   let inputRef: HTMLInputElement | undefined;
@@ -49,7 +49,9 @@ export function NoteFinder(props: {
         contextPath,
       );
       const filtered = props.excludePath
-        ? searchResults.filter((note) => note.path !== props.excludePath)
+        ? searchResults.filter(
+            (result) => result.note.path !== props.excludePath,
+          )
         : searchResults;
       setResults(filtered);
       setSelectedIndex(0);
@@ -96,7 +98,7 @@ export function NoteFinder(props: {
         if (resultCount > 0) {
           const selected = results()[selectedIndex()];
           if (selected) {
-            props.onSelect(selected);
+            props.onSelect(selected.note);
             handleClose();
           }
         }
@@ -142,16 +144,21 @@ export function NoteFinder(props: {
           </div>
         )}
         <For each={results()}>
-          {(note, index) => {
-            const path = note.path || "(root)";
-            const lastSlashIndex = path.lastIndexOf("/");
-            const hasParent = lastSlashIndex !== -1;
-            const parentPath = hasParent
-              ? path.substring(0, lastSlashIndex + 1)
-              : "";
-            const noteName = hasParent
-              ? path.substring(lastSlashIndex + 1)
-              : path;
+          {(result, index) => {
+            const path = result.note.path || "(root)";
+            const chars = Array.from(path);
+            const matched = new Set(result.match_indices);
+            const nameStart = chars.lastIndexOf("/") + 1;
+
+            // Render a range of the path with matched characters emphasized
+            const highlight = (start: number, end: number) =>
+              chars.slice(start, end).map((char, i) =>
+                matched.has(start + i) ? (
+                  <span class="text-text">{char}</span>
+                ) : (
+                  char
+                ),
+              );
 
             return (
               <button
@@ -159,11 +166,13 @@ export function NoteFinder(props: {
                 classList={{
                   underline: index() === selectedIndex(),
                 }}
-                onClick={() => handleResultClick(note)}
+                onClick={() => handleResultClick(result.note)}
                 onMouseEnter={() => setSelectedIndex(index())}
               >
-                {hasParent && <span class="opacity-50">{parentPath}</span>}
-                {noteName}
+                {nameStart > 0 && (
+                  <span class="opacity-50">{highlight(0, nameStart)}</span>
+                )}
+                {highlight(nameStart, chars.length)}
               </button>
             );
           }}
@@ -1,8 +1,14 @@
+export type PropertyValue = string | string[];
+
+export type Properties = Record<string, PropertyValue>;
+
 export interface Note {
   id: number;
   path: string;
   content: string;
   modified: number;
+  properties: Properties | null;
+  content_hash: string;
 }
 
 export interface NoteMetadata {
@@ -10,6 +16,110 @@ export interface NoteMetadata {
   path: string;
   modified: number;
   archived: boolean;
+  properties: Properties | null;
+}
+
+export interface NoteLink {
+  source_path: string;
+  target_path: string;
+  kind: "wiki" | "markdown";
+  resolved: boolean;
+}
+
+/** Byte range within a string */
+export interface MatchRange {
+  start: number;
+  end: number;
+}
+
+export interface SearchHit {
+  note: NoteMetadata;
+  snippet: string;
+  snippet_matches: MatchRange[];
+  matches: MatchRange[];
+  bm25: number;
+}
+
+export interface FuzzyMatch {
+  note: NoteMetadata;
+  score: number;
+  /** Character indices in note.path that matched the query */
+  match_indices: number[];
+  in_note_name: boolean;
+}
+
+export interface UnifiedSearchResult {
+  note: NoteMetadata;
+  source: "path" | "content" | "both";
+  score: number;
+}
+
+export type SaveResult =
+  | { status: "saved"; content_hash: string | null }
+  | { status: "conflict"; disk_content: string; disk_hash: string };
+
+export type MergeRegion =
+  | { type: "resolved"; lines: string[] }
+  | { type: "conflict"; base: string[]; ours: string[]; theirs: string[] };
+
+export type MergeOutcome =
+  | { status: "clean"; content: string }
+  | { status: "conflicts"; regions: MergeRegion[] }
+  | { status: "marked"; content: string };
+
+export interface Unarchived {
+  /** Where the note was restored (or merged) to */
+  path: string;
+  /** Paths of notes whose content was rewritten */
+  rewritten: string[];
+}
+
+export interface ArchiveRule {
+  id: number;
+  /** Children of this note are archived; null for root-level notes */
+  parent: string | null;
+  inactive_secs: number;
+}
+
+export interface RuleMatch {
+  rule_id: number;
+  path: string;
+  /** Unix timestamp of the last access or modification */
+  last_active: number;
+}
+
+/** Progress of the startup sync, reported while the app loads */
+export interface SyncProgress {
+  done: number;
+  total: number;
+}
+
+/** A change the filesystem watcher saw, emitted in batches on "notes:changed" */
+export type WatcherEvent =
+  | { kind: "created"; path: string }
+  | { kind: "modified"; path: string; old_hash: string; new_hash: string }
+  | { kind: "deleted"; path: string }
+  | { kind: "moved"; from: string; to: string }
+  | { kind: "archived"; path: string }
+  | { kind: "frecency_updated" };
+
+export interface Revision {
+  id: number;
+  created_at: number;
+  size: number;
+}
+
+export interface DiffHunk {
+  kind: "added" | "removed" | "unchanged";
+  /** 1-based line numbers where the hunk starts in the old and new text */
+  old_start: number;
+  new_start: number;
+  lines: string[];
+}
+
+export interface TagCount {
+  tag: string;
+  count: number;
 }
 
 export type NotesError =
@@ -18,4 +128,8 @@ export type NotesError =
   | { type: "DatabaseCorrupted" }
   | { type: "NotFound"; path: string }
   | { type: "AlreadyExists"; path: string }
-  | { type: "ParentNotFound"; path: string };
+  | { type: "ParentNotFound"; path: string }
+  | { type: "InvalidTag"; tag: string }
+  | { type: "InvalidProperty"; key: string }
+  | { type: "InvalidQuery"; position: number; message: string }
+  | { type: "Conflict"; disk_content: string; disk_hash: string };